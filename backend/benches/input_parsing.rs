@@ -0,0 +1,67 @@
+/// Benchmarks accumulating the rounds and reading the input files, on files written by the fixture generator
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BatchSize, BenchmarkGroup, BenchmarkId,
+    Criterion, SamplingMode,
+};
+use defispring::api::{
+    generator::{generate_allocations, write_fixture},
+    processor::{map_cumulative_amounts, read_allocations},
+    structs::{CampaignConfig, RoundAmounts},
+};
+use std::{env, time::Duration};
+
+/// Same sizes as the tree benches
+const SIZES: [u64; 3] = [1_000, 100_000, 1_000_000];
+
+/// 1M rows take seconds to read, so those samples use flat sampling and a short warm-up
+fn large_input(group: &mut BenchmarkGroup<WallTime>, count: u64) {
+    if count >= 1_000_000 {
+        group.sampling_mode(SamplingMode::Flat);
+        group.warm_up_time(Duration::from_secs(1));
+    }
+}
+
+fn accumulation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_cumulative_amounts");
+    group.sample_size(10);
+    let config = CampaignConfig::default();
+
+    for count in SIZES {
+        large_input(&mut group, count);
+        // Two rounds with the same addresses
+        let amounts = generate_allocations(count);
+        let rounds: Vec<RoundAmounts> = (1..=2)
+            .map(|round| RoundAmounts {
+                round,
+                amounts: amounts.clone(),
+            })
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(count), &rounds, |b, rounds| {
+            b.iter_batched(
+                || rounds.clone(),
+                |rounds| map_cumulative_amounts(rounds, &config),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn input_files(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_allocations");
+    group.sample_size(10);
+
+    for count in SIZES {
+        large_input(&mut group, count);
+        let folder = env::temp_dir().join(format!("defispring_bench_{}", count));
+        write_fixture(&folder, 1, count).unwrap();
+        let folder = folder.to_str().unwrap().to_string();
+        group.bench_with_input(BenchmarkId::from_parameter(count), &folder, |b, folder| {
+            b.iter(|| read_allocations(folder.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, accumulation, input_files);
+criterion_main!(benches);
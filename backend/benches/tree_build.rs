@@ -0,0 +1,67 @@
+/// Benchmarks building Merkle trees and looking up proofs.
+/// Compare `cargo bench --bench tree_build` to `cargo bench --bench tree_build --features parallel`
+/// to see the speedup of the parallel build
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId, Criterion,
+    SamplingMode,
+};
+use defispring::api::{
+    merkle_tree::felt_to_b16,
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use starknet_crypto::FieldElement;
+use std::time::Duration;
+
+/// 1k, 100k and 1M addresses
+const SIZES: [u64; 3] = [1_000, 100_000, 1_000_000];
+
+/// A 1M tree takes seconds to build, so those samples use flat sampling and a short warm-up
+fn large_input(group: &mut BenchmarkGroup<WallTime>, count: u64) {
+    if count >= 1_000_000 {
+        group.sampling_mode(SamplingMode::Flat);
+        group.warm_up_time(Duration::from_secs(1));
+    }
+}
+
+fn allocations(count: u64) -> Vec<CumulativeAllocation> {
+    (1..=count)
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i),
+            cumulative_amount: i as u128,
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn tree_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_build");
+    group.sample_size(10);
+
+    for count in SIZES {
+        large_input(&mut group, count);
+        let input = allocations(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &input, |b, input| {
+            b.iter(|| MerkleTree::new(input.clone()))
+        });
+    }
+    group.finish();
+}
+
+fn address_calldata(c: &mut Criterion) {
+    let mut group = c.benchmark_group("address_calldata");
+
+    for count in SIZES {
+        let tree = MerkleTree::new(allocations(count));
+        // An address in the middle of the tree
+        let address = felt_to_b16(&FieldElement::from(count / 2));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &address,
+            |b, address| b.iter(|| tree.address_calldata(address).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tree_build, address_calldata);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+use defispring::api::address::{parse_optional_address, Address};
+use libfuzzer_sys::fuzz_target;
+use std::str::FromStr;
+
+// A parsed address has to parse back to itself from its canonical form
+fuzz_target!(|data: &str| {
+    if let Ok(address) = Address::from_str(data) {
+        let canonical = address.to_string();
+        assert_eq!(canonical.len(), 66);
+        assert_eq!(Address::from_str(&canonical), Ok(address));
+    }
+    let _ = parse_optional_address(Some(data));
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use defispring::api::{address::Address, processor::parse_allocations};
+use libfuzzer_sys::fuzz_target;
+use std::str::FromStr;
+
+// Parses the rows of an input file and their addresses like the accumulation does
+fuzz_target!(|data: &[u8]| {
+    if let Ok(rows) = parse_allocations(data) {
+        for row in rows {
+            let _ = Address::from_str(&row.address);
+            let _ = row.amount.parse::<u128>();
+        }
+    }
+});
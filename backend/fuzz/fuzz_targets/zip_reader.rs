@@ -0,0 +1,10 @@
+#![no_main]
+
+use defispring::api::processor::read_zip_allocations;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+// Invalid archives have to give an error instead of a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = read_zip_allocations(Cursor::new(data));
+});
@@ -0,0 +1,81 @@
+use starknet_crypto::FieldElement;
+use std::{fmt, str::FromStr};
+
+use super::errors::{ApiError, ErrorKind};
+
+/// A Starknet contract address: a felt below 2^251.
+/// Parsed from hex or decimal, so "0x01", "0x1" and "1" are the same address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address(FieldElement);
+
+impl Address {
+    pub fn felt(&self) -> FieldElement {
+        self.0
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("Address is empty".to_string());
+        }
+        let felt = FieldElement::from_str(value).map_err(|e| e.to_string())?;
+        Address::try_from(felt)
+    }
+}
+
+impl TryFrom<FieldElement> for Address {
+    type Error = String;
+
+    fn try_from(felt: FieldElement) -> Result<Self, Self::Error> {
+        // Below 2^251 means the highest 5 bits of the 256 bit representation are zero
+        if felt.to_bytes_be()[0] >= 0x08 {
+            return Err("Address is out of range".to_string());
+        }
+        Ok(Address(felt))
+    }
+}
+
+impl From<Address> for FieldElement {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+/// The canonical format: 0x followed by 64 hex digits
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_address(&self.0))
+    }
+}
+
+/// Formats a felt holding an address like `Address`, e.g. the address or token of a leaf
+pub fn format_address(felt: &FieldElement) -> String {
+    let hex: String = felt
+        .to_bytes_be()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("0x{}", hex)
+}
+
+/// Parses an optional address, e.g. the token of a row or a query
+pub fn parse_optional_address(value: Option<&str>) -> Result<Option<FieldElement>, String> {
+    value
+        .map(|v| Address::from_str(v).map(|a| a.felt()))
+        .transpose()
+}
+
+/// Parses the address and the optional token of a query
+pub fn parse_query(
+    address: &str,
+    token: Option<&str>,
+) -> Result<(FieldElement, Option<FieldElement>), ApiError> {
+    let invalid = |e: String| ApiError::new(ErrorKind::InvalidAddress, e);
+    let address = Address::from_str(address).map_err(invalid)?.felt();
+    let token = parse_optional_address(token).map_err(invalid)?;
+    Ok((address, token))
+}
@@ -0,0 +1,1039 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{HttpServiceFactory, Service, ServiceRequest, ServiceResponse, Transform},
+    get,
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    post, web, Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::to_string;
+use starknet_crypto::FieldElement;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    env,
+    fs::{self, File, OpenOptions},
+    future::{ready, Future, Ready},
+    hash::{Hash, Hasher},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info, warn};
+
+use super::{
+    address::{parse_optional_address, Address},
+    data_storage::{get_all_data, get_api_data, set_round_states, update_data, INPUT_FOLDER},
+    lifecycle::{check_transition, is_served, round_state, write_round_states},
+    merkle_tree::felt_to_b16,
+    metrics::record_reload,
+    processor::{
+        read_campaign_config, read_round_files, read_zip_allocations, retrieve_valid_files,
+        transform_allocations_to_cumulative_rounds,
+    },
+    structs::{
+        CampaignConfig, JSONAllocation, RoundAmounts, RoundState, RoundTreeData, Visibility,
+    },
+    vesting::{merge_vesting_rounds, read_vesting_schedules},
+};
+
+/// Invalid rows listed in a validation report. The rest are only counted
+const MAX_REPORTED_ERRORS: usize = 100;
+const DEFAULT_MAX_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+
+/// Settings of the admin API, read from the environment
+#[derive(Debug, Clone)]
+pub struct AdminConfig {
+    /// API keys with their names, from ADMIN_API_KEYS. Only the names are written to the audit log
+    pub keys: Vec<(String, String)>,
+    /// Where promoted rounds are stored. Uploaded rounds wait in its staging subfolder
+    pub input_folder: String,
+    /// The audit log, from ADMIN_AUDIT_LOG. Defaults to ./admin_audit.log
+    pub audit_log: String,
+    /// Largest round file that can be uploaded, from ADMIN_MAX_UPLOAD_BYTES. Defaults to 512 MiB
+    pub max_upload_bytes: usize,
+}
+
+impl AdminConfig {
+    /// None if ADMIN_API_KEYS isn't set, in which case the admin API isn't served
+    pub fn from_env() -> Option<Self> {
+        let keys = parse_api_keys(&env::var("ADMIN_API_KEYS").ok()?);
+        if keys.is_empty() {
+            return None;
+        }
+        let max_upload_bytes = env::var("ADMIN_MAX_UPLOAD_BYTES")
+            .ok()
+            .map(|v| v.parse::<usize>().expect("Invalid ADMIN_MAX_UPLOAD_BYTES"))
+            .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
+        Some(AdminConfig {
+            keys,
+            input_folder: INPUT_FOLDER.to_string(),
+            audit_log: env::var("ADMIN_AUDIT_LOG")
+                .unwrap_or_else(|_| "./admin_audit.log".to_string()),
+            max_upload_bytes,
+        })
+    }
+
+    /// Where an uploaded round waits until it's promoted
+    pub fn staged_file(&self, round: u8) -> PathBuf {
+        Path::new(&self.input_folder)
+            .join("staging")
+            .join(format!("raw_{}.zip", round))
+    }
+}
+
+/// Parses comma separated API keys, each given as name:key or only as the key.
+/// Keys without a name are named by their position, e.g. key2
+pub fn parse_api_keys(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .map(str::trim)
+        .enumerate()
+        .map(|(i, entry)| match entry.split_once(':') {
+            Some((name, key)) => (name.trim().to_string(), key.trim().to_string()),
+            None => (format!("key{}", i + 1), entry.to_string()),
+        })
+        .filter(|(_, key)| !key.is_empty())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Ok,
+    /// The request had no valid API key
+    Denied,
+    Failed,
+}
+
+/// One line of the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Unix time in seconds
+    pub time: u64,
+    /// Name of the API key, or "unknown" for requests without a valid key
+    pub actor: String,
+    pub action: String,
+    pub round: Option<u8>,
+    pub outcome: AuditOutcome,
+    pub detail: Option<String>,
+}
+
+/// Denied requests of one action are written at most once in this interval. The others are counted
+const DENIED_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Denied requests of one action that weren't written yet
+#[derive(Debug)]
+struct DeniedRequests {
+    last_written: Instant,
+    skipped: u64,
+}
+
+/// Log of all admin requests. Entries are only ever appended.
+/// Denied requests are aggregated, so clients without a key can't fill the log
+#[derive(Debug)]
+pub struct AuditLog {
+    file: Mutex<File>,
+    denied: Mutex<HashMap<String, DeniedRequests>>,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> Self {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("Failed to open audit log");
+        AuditLog {
+            file: Mutex::new(file),
+            denied: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(
+        &self,
+        actor: &str,
+        action: &str,
+        round: Option<u8>,
+        outcome: AuditOutcome,
+        mut detail: Option<String>,
+    ) {
+        if outcome == AuditOutcome::Denied {
+            match self.throttle_denied(action) {
+                None => return,
+                Some(0) => (),
+                Some(skipped) => {
+                    detail = Some(format!(
+                        "{} ({} more denied requests since the last entry)",
+                        detail.unwrap_or_default(),
+                        skipped
+                    ))
+                }
+            }
+        }
+        let entry = AuditEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            round,
+            outcome,
+            detail,
+        };
+        info!(actor, action, round, outcome = ?outcome, detail = ?entry.detail, "Admin request");
+
+        let line = to_string(&entry).expect("Failed to serialize audit entry") + "\n";
+        let mut file = self.file.lock().expect("Failed to acquire lock");
+        // A single write per entry, so entries are never interleaved. Denied requests aren't synced
+        let result = file.write_all(line.as_bytes()).and_then(|_| match outcome {
+            AuditOutcome::Denied => Ok(()),
+            _ => file.sync_data(),
+        });
+        if let Err(e) = result {
+            error!(error = %e, action, "Failed to write audit log");
+        }
+    }
+
+    /// Whether a denied request is written. None if it's only counted,
+    /// otherwise the number of denied requests counted since the last written one
+    fn throttle_denied(&self, action: &str) -> Option<u64> {
+        let mut denied = self.denied.lock().expect("Failed to acquire lock");
+        let now = Instant::now();
+        match denied.get_mut(action) {
+            Some(requests) if now.duration_since(requests.last_written) < DENIED_LOG_INTERVAL => {
+                requests.skipped += 1;
+                None
+            }
+            Some(requests) => {
+                requests.last_written = now;
+                Some(std::mem::take(&mut requests.skipped))
+            }
+            None => {
+                denied.insert(
+                    action.to_string(),
+                    DeniedRequests {
+                        last_written: now,
+                        skipped: 0,
+                    },
+                );
+                Some(0)
+            }
+        }
+    }
+}
+
+/// Reads all entries of an audit log
+pub fn read_audit_log(path: &str) -> Vec<AuditEntry> {
+    let contents = fs::read_to_string(path).expect("Failed to read audit log");
+    contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Invalid audit entry"))
+        .collect()
+}
+
+/// A row of a round file that the backend can't use
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// Position of the row in the file, from 0
+    pub row: usize,
+    pub error: String,
+}
+
+/// The round as it would be served after the promotion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildSummary {
+    pub root: String,
+    /// Empty if an earlier round is missing
+    pub slot: Option<u64>,
+    pub leaves: usize,
+    pub round_total_amount: String,
+    pub accumulated_total_amount: String,
+    /// How long the dry run took, including reading the other rounds
+    pub duration_ms: u64,
+}
+
+/// Result of checking a staged round and building it in a dry run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub round: u8,
+    /// Whether the round can be promoted
+    pub valid: bool,
+    pub rows: usize,
+    /// Rows with an invalid address, amount, token or extra field
+    pub invalid_rows: usize,
+    /// The first invalid rows
+    pub errors: Vec<RowError>,
+    /// Addresses with several rows for the same token. Their amounts are added up
+    pub repeated_addresses: usize,
+    /// Why the built round can't be promoted, e.g. it would change a published root
+    pub build_errors: Vec<String>,
+    /// Empty if the rows are invalid
+    pub build: Option<BuildSummary>,
+}
+
+/// Checks a row the way the rows are read when the rounds are built.
+/// Returns the address and the token, as the rows of the same address and token are added up
+fn check_row(row: &JSONAllocation) -> Result<(FieldElement, Option<FieldElement>), String> {
+    let address = Address::from_str(&row.address)
+        .map_err(|e| format!("Invalid address {:?}: {}", row.address, e))?
+        .felt();
+    // The build would use 0 for these amounts
+    row.amount
+        .parse::<u128>()
+        .map_err(|_| format!("Invalid amount {:?}", row.amount))?;
+    let token = parse_optional_address(row.token.as_deref())
+        .map_err(|e| format!("Invalid token {:?}: {}", row.token, e))?;
+    for field in row.extra_fields.iter() {
+        FieldElement::from_str(field).map_err(|_| format!("Invalid extra field {:?}", field))?;
+    }
+    Ok((address, token))
+}
+
+/// Checks the rows of a round and builds all rounds with them in a dry run. The served rounds aren't changed.
+/// The other rounds and the campaign settings are read from the input folder.
+/// Returns the report, and the built rounds if the round can be promoted
+pub fn validate_round(
+    input_folder: &str,
+    round: u8,
+    rows: Vec<JSONAllocation>,
+) -> (ValidationReport, Option<Vec<RoundTreeData>>) {
+    let mut report = ValidationReport {
+        round,
+        valid: false,
+        rows: rows.len(),
+        invalid_rows: 0,
+        errors: vec![],
+        repeated_addresses: 0,
+        build_errors: vec![],
+        build: None,
+    };
+
+    let mut keys = HashSet::new();
+    for (i, row) in rows.iter().enumerate() {
+        match check_row(row) {
+            Ok(key) => {
+                if !keys.insert(key) {
+                    report.repeated_addresses += 1;
+                }
+            }
+            Err(error) => {
+                report.invalid_rows += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    report.errors.push(RowError { row: i, error });
+                }
+            }
+        }
+    }
+    if rows.is_empty() {
+        report.build_errors.push("The file has no rows".to_string());
+    }
+    if !report.build_errors.is_empty() || report.invalid_rows > 0 {
+        return (report, None);
+    }
+
+    let start = Instant::now();
+    let config = read_campaign_config(input_folder);
+    let mut round_amounts: Vec<RoundAmounts> = read_round_files(input_folder.to_string())
+        .into_iter()
+        .filter(|r| r.round != round)
+        .collect();
+    round_amounts.push(RoundAmounts {
+        round,
+        amounts: rows,
+    });
+    let merge = merge_vesting_rounds(&mut round_amounts, &read_vesting_schedules(input_folder));
+    if !merge.invalid.is_empty() {
+        report.build_errors = merge
+            .invalid
+            .iter()
+            .map(|(index, e)| format!("Invalid vesting schedule {}: {}", index, e))
+            .collect();
+        return (report, None);
+    }
+    let rounds = transform_allocations_to_cumulative_rounds(round_amounts, &config);
+
+    report.build_errors = check_build(&rounds, round, &config);
+    report.build = rounds
+        .iter()
+        .find(|r| r.round == round)
+        .map(|r| BuildSummary {
+            root: felt_to_b16(&r.tree.root),
+            slot: r.slot,
+            leaves: r.tree.allocations.len(),
+            round_total_amount: r.round_total_amount.to_string(),
+            accumulated_total_amount: r.accumulated_total_amount.to_string(),
+            duration_ms: start.elapsed().as_millis() as u64,
+        });
+    report.valid = report.build_errors.is_empty();
+    match report.valid {
+        true => (report, Some(rounds)),
+        false => (report, None),
+    }
+}
+
+/// Reasons the rounds of a dry run can't be served
+fn check_build(rounds: &[RoundTreeData], round: u8, config: &CampaignConfig) -> Vec<String> {
+    let mut errors = vec![];
+    match rounds.iter().find(|r| r.round == round) {
+        None => errors.push(format!("Round {} has no leaves", round)),
+        Some(r) if r.slot.is_none() => errors.push(format!(
+            "Round {} has missing predecessors and wouldn't be served",
+            round
+        )),
+        Some(_) => (),
+    }
+    if config.round_states.get(&round) == Some(&RoundState::Retired) {
+        errors.push(format!("Round {} is retired", round));
+    }
+    // Replacing a round also changes the roots of the rounds after it
+    for r in rounds.iter() {
+        if let Some(published) = r.slot.and_then(|s| config.published_roots.get(s as usize)) {
+            if *published != r.tree.root {
+                errors.push(format!(
+                    "Round {} would change the root already published in slot {}",
+                    r.round,
+                    r.slot.unwrap_or_default()
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Name of the API key of a request, added by AdminAuth
+#[derive(Debug, Clone)]
+pub struct AdminActor(pub String);
+
+#[derive(Debug)]
+struct AdminError {
+    status: StatusCode,
+    message: String,
+}
+
+impl AdminError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        AdminError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+fn check_round(round: u8) -> Result<(), AdminError> {
+    match round {
+        0 => Err(AdminError::new(
+            StatusCode::BAD_REQUEST,
+            "Round must be between 1 and 255",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn internal_error(e: impl ToString) -> AdminError {
+    AdminError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn file_hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The rounds built in a dry run, and the served rounds they were built next to
+struct DryRun {
+    base: Arc<Vec<Arc<RoundTreeData>>>,
+    rounds: Vec<RoundTreeData>,
+}
+
+struct Validation {
+    report: ValidationReport,
+    /// Hash of the staged file that was validated
+    file_hash: u64,
+    /// Only kept for valid rounds
+    dry_run: Option<DryRun>,
+}
+
+/// A built round and its state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoundStateResult {
+    pub round: u8,
+    pub slot: Option<u64>,
+    pub root: String,
+    pub state: RoundState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateChange {
+    pub state: RoundState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UploadResult {
+    pub round: u8,
+    pub rows: usize,
+}
+
+/// Shared by the admin endpoints of all workers
+pub struct AdminState {
+    config: AdminConfig,
+    audit: AuditLog,
+    /// Validations of the staged rounds by round. Also held by the actions that change the served rounds,
+    /// so they run one at a time
+    staged: Mutex<HashMap<u8, Validation>>,
+}
+
+impl AdminState {
+    pub fn new(config: AdminConfig) -> Self {
+        AdminState {
+            audit: AuditLog::open(&config.audit_log),
+            config,
+            staged: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u8, Validation>> {
+        self.staged.lock().expect("Failed to acquire lock")
+    }
+
+    /// Finds the name of an API key. The key is compared with every key, without stopping at a match,
+    /// so the time doesn't tell which key matched or how long the keys are
+    fn actor(&self, key: &str) -> Option<String> {
+        self.config
+            .keys
+            .iter()
+            .fold(None, |found, (name, k)| {
+                let matches = constant_time_eq(k.as_bytes(), key.as_bytes());
+                found.or(matches.then_some(name))
+            })
+            .cloned()
+    }
+
+    /// Writes the result of an action to the audit log and responds with it
+    fn respond<T: Serialize>(
+        &self,
+        req: &HttpRequest,
+        action: &str,
+        round: Option<u8>,
+        result: Result<(T, Option<String>), AdminError>,
+    ) -> HttpResponse {
+        let actor = req
+            .extensions()
+            .get::<AdminActor>()
+            .map(|a| a.0.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        match result {
+            Ok((value, detail)) => {
+                self.audit
+                    .record(&actor, action, round, AuditOutcome::Ok, detail);
+                HttpResponse::Ok().json(value)
+            }
+            Err(e) => {
+                self.audit.record(
+                    &actor,
+                    action,
+                    round,
+                    AuditOutcome::Failed,
+                    Some(e.message.clone()),
+                );
+                HttpResponse::build(e.status).json(e.message)
+            }
+        }
+    }
+
+    fn upload(&self, round: u8, body: &[u8]) -> Result<(UploadResult, Option<String>), AdminError> {
+        check_round(round)?;
+        let rows = match read_zip_allocations(Cursor::new(body)) {
+            Ok(Some(rows)) => rows.len(),
+            Ok(None) => {
+                return Err(AdminError::new(
+                    StatusCode::BAD_REQUEST,
+                    "The archive is empty",
+                ))
+            }
+            Err(e) => {
+                return Err(AdminError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid round file: {}", e),
+                ))
+            }
+        };
+
+        let mut staged = self.lock();
+        let path = self.config.staged_file(round);
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(internal_error)?;
+        }
+        // Renamed once complete, so a partly written file is never validated
+        let partial = path.with_extension("zip.partial");
+        fs::write(&partial, body).map_err(internal_error)?;
+        fs::rename(&partial, &path).map_err(internal_error)?;
+        // The previous validation was of the replaced file
+        staged.remove(&round);
+
+        Ok((UploadResult { round, rows }, Some(format!("{} rows", rows))))
+    }
+
+    /// The file of a round in the input folder
+    fn input_file(&self, round: u8) -> Option<PathBuf> {
+        retrieve_valid_files(self.config.input_folder.clone())
+            .into_iter()
+            .find(|f| f.round == round)
+            .map(|f| PathBuf::from(f.full_path))
+    }
+
+    /// The rows of a round file and the hash of the file
+    fn read_rows(
+        &self,
+        round: u8,
+        path: Option<PathBuf>,
+    ) -> Result<(Vec<JSONAllocation>, u64), AdminError> {
+        let contents = path.and_then(|p| fs::read(p).ok()).ok_or_else(|| {
+            AdminError::new(
+                StatusCode::NOT_FOUND,
+                format!("Round {} is neither staged nor in the input folder", round),
+            )
+        })?;
+        // A corrupt archive is rejected like in the upload
+        let rows = read_zip_allocations(Cursor::new(&contents))
+            .map_err(|e| {
+                AdminError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid round file: {}", e),
+                )
+            })?
+            .unwrap_or_default();
+        Ok((rows, file_hash(&contents)))
+    }
+
+    /// Validates the staged file of a round or, if there's none, the file the round was built from.
+    /// A draft built from a valid file becomes validated
+    async fn validate(&self, round: u8) -> Result<(ValidationReport, Option<String>), AdminError> {
+        check_round(round)?;
+        let staged_file = self.config.staged_file(round);
+        let staged = staged_file.exists();
+        let path = match staged {
+            true => Some(staged_file),
+            false => self.input_file(round),
+        };
+        let (rows, hash) = self.read_rows(round, path)?;
+        let base = get_all_data();
+        let folder = self.config.input_folder.clone();
+        // Building the trees takes long, so it doesn't run on the worker's thread
+        let (report, rounds) = web::block(move || validate_round(&folder, round, rows))
+            .await
+            .map_err(internal_error)?;
+        let mut staged_rounds = self.lock();
+        let report = match staged {
+            true => report,
+            false => self.mark_validated(report)?,
+        };
+
+        let detail = match report.valid {
+            true => "valid".to_string(),
+            false => format!(
+                "invalid: {} invalid rows, {} build errors",
+                report.invalid_rows,
+                report.build_errors.len()
+            ),
+        };
+        // Only staged rounds can be promoted
+        staged_rounds.insert(
+            round,
+            Validation {
+                report: report.clone(),
+                file_hash: hash,
+                dry_run: rounds
+                    .filter(|_| staged)
+                    .map(|rounds| DryRun { base, rounds }),
+            },
+        );
+        Ok((report, Some(detail)))
+    }
+
+    /// Marks a draft as validated if its file passed validation and is the one it was built from
+    fn mark_validated(&self, mut report: ValidationReport) -> Result<ValidationReport, AdminError> {
+        if !report.valid {
+            return Ok(report);
+        }
+        let data = get_api_data();
+        let served = data.rounds.iter().find(|r| r.round == report.round);
+        let root = served.map(|r| felt_to_b16(&r.tree.root));
+        if root.is_none() || root != report.build.as_ref().map(|b| b.root.clone()) {
+            report.valid = false;
+            report.build_errors.push(format!(
+                "Round {} was built from another file, upload the file to replace it",
+                report.round
+            ));
+            return Ok(report);
+        }
+
+        if served.map(|r| round_state(r, &data.config)) == Some(RoundState::Draft) {
+            let mut states = data.config.round_states.clone();
+            states.insert(report.round, RoundState::Validated);
+            self.store_states(states)?;
+        }
+        Ok(report)
+    }
+
+    /// Stores the states of the rounds in the input folder and uses them right away
+    fn store_states(&self, states: BTreeMap<u8, RoundState>) -> Result<(), AdminError> {
+        write_round_states(&self.config.input_folder, &states).map_err(internal_error)?;
+        set_round_states(states);
+        Ok(())
+    }
+
+    fn report(&self, round: u8) -> Result<(ValidationReport, Option<String>), AdminError> {
+        check_round(round)?;
+        match self.lock().get(&round) {
+            Some(validation) => Ok((validation.report.clone(), None)),
+            None => Err(AdminError::new(
+                StatusCode::NOT_FOUND,
+                format!("Round {} has no validation report", round),
+            )),
+        }
+    }
+
+    /// Moves a validated round into the input folder and replaces the rounds with the ones of its dry run.
+    /// The round is validated and has to be approved to be served. So do approved rounds whose root changed
+    fn promote(&self, round: u8) -> Result<(BuildSummary, Option<String>), AdminError> {
+        check_round(round)?;
+        let mut staged = self.lock();
+        let path = self.config.staged_file(round);
+        {
+            let validation = staged.get(&round).ok_or_else(|| {
+                AdminError::new(
+                    StatusCode::NOT_FOUND,
+                    format!("Round {} hasn't been validated", round),
+                )
+            })?;
+            let dry_run = match (&validation.dry_run, validation.report.valid) {
+                (Some(dry_run), true) => dry_run,
+                _ => {
+                    return Err(AdminError::new(
+                        StatusCode::CONFLICT,
+                        format!("Round {} didn't pass validation", round),
+                    ))
+                }
+            };
+            if !Arc::ptr_eq(&dry_run.base, &get_all_data()) {
+                return Err(AdminError::new(
+                    StatusCode::CONFLICT,
+                    "The served rounds changed since the dry run, validate the round again",
+                ));
+            }
+            let contents = fs::read(&path).map_err(internal_error)?;
+            if file_hash(&contents) != validation.file_hash {
+                return Err(AdminError::new(
+                    StatusCode::CONFLICT,
+                    "The staged file changed since it was validated, validate the round again",
+                ));
+            }
+        }
+
+        // Other files of the same round, e.g. RAW_05.zip, would be read next to the new one
+        let target = Path::new(&self.config.input_folder).join(format!("raw_{}.zip", round));
+        for file in retrieve_valid_files(self.config.input_folder.clone()) {
+            if file.round == round && Path::new(&file.full_path) != target {
+                fs::remove_file(&file.full_path).map_err(internal_error)?;
+            }
+        }
+        fs::rename(&path, &target).map_err(internal_error)?;
+
+        let validation = staged.remove(&round).expect("Validation was checked");
+        let dry_run = validation.dry_run.expect("Dry run was checked");
+        let mut states = get_api_data().config.round_states.clone();
+        for r in dry_run.rounds.iter() {
+            let changed = match dry_run.base.iter().find(|b| b.round == r.round) {
+                Some(before) => before.tree.root != r.tree.root,
+                None => true,
+            };
+            if r.round == round || (changed && states.get(&r.round) == Some(&RoundState::Approved))
+            {
+                states.insert(r.round, RoundState::Validated);
+            }
+        }
+        write_round_states(&self.config.input_folder, &states).map_err(internal_error)?;
+
+        // The new rounds and their states are swapped in together
+        let memory_bytes = dry_run.rounds.iter().map(|r| r.estimated_size()).sum();
+        let rounds = Arc::new(dry_run.rounds.into_iter().map(Arc::new).collect());
+        update_data(|data| {
+            data.rounds = Arc::clone(&rounds);
+            data.config.round_states = states.clone();
+        });
+        record_reload(memory_bytes);
+
+        let build = validation.report.build.expect("Valid rounds are built");
+        let detail = format!("root {}", build.root);
+        Ok((build, Some(detail)))
+    }
+
+    /// Changes the state of a built round, e.g. approves it so it's served
+    fn set_state(
+        &self,
+        round: u8,
+        to: RoundState,
+    ) -> Result<(RoundStateResult, Option<String>), AdminError> {
+        check_round(round)?;
+        let _staged = self.lock();
+        let api_data = get_api_data();
+        let (rounds, config) = (&api_data.rounds, &api_data.config);
+        let data = rounds.iter().find(|r| r.round == round).ok_or_else(|| {
+            AdminError::new(
+                StatusCode::NOT_FOUND,
+                format!("Round {} isn't built", round),
+            )
+        })?;
+        let from = round_state(data, config);
+        check_transition(from, to).map_err(|e| AdminError::new(StatusCode::CONFLICT, e))?;
+
+        // The root of a round includes the amounts of the rounds before it
+        if to == RoundState::Approved {
+            let unreviewed = rounds.iter().find(|r| {
+                r.round < round
+                    && r.slot.is_some()
+                    && !is_served(round_state(r, config), Visibility::Served)
+                    && round_state(r, config) != RoundState::Retired
+            });
+            if let Some(r) = unreviewed {
+                return Err(AdminError::new(
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Round {} has to be approved before round {}",
+                        r.round, round
+                    ),
+                ));
+            }
+        }
+
+        let mut states = config.round_states.clone();
+        states.insert(round, to);
+        self.store_states(states)?;
+        Ok((
+            round_state_result(data, to),
+            Some(format!("{} -> {}", from, to)),
+        ))
+    }
+
+    /// All built rounds with their states, including the ones that aren't served
+    fn rounds(&self) -> Result<(Vec<RoundStateResult>, Option<String>), AdminError> {
+        let data = get_api_data();
+        let rounds = data
+            .rounds
+            .iter()
+            .map(|r| round_state_result(r, round_state(r, &data.config)))
+            .collect();
+        Ok((rounds, None))
+    }
+}
+
+fn round_state_result(round: &RoundTreeData, state: RoundState) -> RoundStateResult {
+    RoundStateResult {
+        round: round.round,
+        slot: round.slot,
+        root: felt_to_b16(&round.tree.root),
+        state,
+    }
+}
+
+/// The key given as "Authorization: Bearer <key>"
+fn bearer_key(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .json("Invalid or missing API key")
+}
+
+/// The rounds a request to a public endpoint can get. Previews also serve drafts and validated rounds,
+/// need one of the admin API keys and are written to the audit log
+pub fn authorize_preview(
+    req: &HttpRequest,
+    preview: Option<bool>,
+    round: Option<u8>,
+) -> Result<Visibility, Box<HttpResponse>> {
+    if preview != Some(true) {
+        return Ok(Visibility::Served);
+    }
+    let state = req
+        .app_data::<web::Data<AdminState>>()
+        .ok_or_else(|| Box::new(HttpResponse::Forbidden().json("Previews need the admin API")))?;
+    let detail = Some(req.path().to_string());
+    match bearer_key(req).and_then(|key| state.actor(key)) {
+        Some(actor) => {
+            state
+                .audit
+                .record(&actor, "preview", round, AuditOutcome::Ok, detail);
+            Ok(Visibility::Preview)
+        }
+        None => {
+            state
+                .audit
+                .record("unknown", "preview", round, AuditOutcome::Denied, detail);
+            Err(Box::new(unauthorized()))
+        }
+    }
+}
+
+/// Compares a given value with an expected one in a time that only depends on the length of the given value
+fn constant_time_eq(expected: &[u8], given: &[u8]) -> bool {
+    let diff = given.iter().enumerate().fold(0, |acc, (i, g)| {
+        acc | (expected.get(i).copied().unwrap_or(0) ^ g) as usize
+    });
+    diff | (expected.len() ^ given.len()) == 0
+}
+
+#[post("/rounds/{round}/upload")]
+async fn admin_upload(
+    req: HttpRequest,
+    state: web::Data<AdminState>,
+    round: web::Path<u8>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let round = round.into_inner();
+    let result = state.upload(round, &body);
+    state.respond(&req, "upload", Some(round), result)
+}
+
+#[post("/rounds/{round}/validate")]
+async fn admin_validate(
+    req: HttpRequest,
+    state: web::Data<AdminState>,
+    round: web::Path<u8>,
+) -> HttpResponse {
+    let round = round.into_inner();
+    let result = state.validate(round).await;
+    state.respond(&req, "validate", Some(round), result)
+}
+
+#[get("/rounds/{round}/report")]
+async fn admin_report(
+    req: HttpRequest,
+    state: web::Data<AdminState>,
+    round: web::Path<u8>,
+) -> HttpResponse {
+    let round = round.into_inner();
+    let result = state.report(round);
+    state.respond(&req, "report", Some(round), result)
+}
+
+#[post("/rounds/{round}/promote")]
+async fn admin_promote(
+    req: HttpRequest,
+    state: web::Data<AdminState>,
+    round: web::Path<u8>,
+) -> HttpResponse {
+    let round = round.into_inner();
+    let result = state.promote(round);
+    state.respond(&req, "promote", Some(round), result)
+}
+
+#[post("/rounds/{round}/state")]
+async fn admin_set_state(
+    req: HttpRequest,
+    state: web::Data<AdminState>,
+    round: web::Path<u8>,
+    change: web::Json<StateChange>,
+) -> HttpResponse {
+    let round = round.into_inner();
+    let result = state.set_state(round, change.state);
+    state.respond(&req, "set_state", Some(round), result)
+}
+
+#[get("/rounds")]
+async fn admin_rounds(req: HttpRequest, state: web::Data<AdminState>) -> HttpResponse {
+    let result = state.rounds();
+    state.respond(&req, "list_rounds", None, result)
+}
+
+/// The /admin endpoints. Every request needs one of the API keys
+pub fn admin_service(state: web::Data<AdminState>) -> impl HttpServiceFactory {
+    web::scope("/admin")
+        .app_data(state.clone())
+        .app_data(web::PayloadConfig::new(state.config.max_upload_bytes))
+        .wrap(AdminAuth { state })
+        .service(admin_upload)
+        .service(admin_validate)
+        .service(admin_report)
+        .service(admin_promote)
+        .service(admin_set_state)
+        .service(admin_rounds)
+}
+
+/// Middleware rejecting requests without a valid API key in the Authorization header
+pub struct AdminAuth {
+    state: web::Data<AdminState>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AdminAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AdminAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminAuthMiddleware {
+            service,
+            state: self.state.clone(),
+        }))
+    }
+}
+
+pub struct AdminAuthMiddleware<S> {
+    service: S,
+    state: web::Data<AdminState>,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let actor = bearer_key(req.request()).and_then(|key| self.state.actor(key));
+
+        match actor {
+            Some(actor) => {
+                req.extensions_mut().insert(AdminActor(actor));
+                let fut = self.service.call(req);
+                Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+            }
+            None => {
+                warn!(path = req.path(), "Admin request without a valid API key");
+                self.state.audit.record(
+                    "unknown",
+                    "authenticate",
+                    None,
+                    AuditOutcome::Denied,
+                    Some(format!("{} {}", req.method(), req.path())),
+                );
+                let res = req.into_response(unauthorized());
+                Box::pin(async move { Ok(res.map_into_right_body()) })
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{
+    http::header::{CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch},
+    HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use super::{
+    address::Address, data_storage::get_api_data, lifecycle::served_rounds,
+    sequencing::select_round, structs::Visibility,
+};
+
+/// Max age in seconds of responses about an explicitly requested round before the latest one. Its root never changes
+pub const PAST_ROUND_MAX_AGE: u32 = 86400;
+/// Max age in seconds of responses about the latest round, which changes when a new round is added
+pub const LATEST_ROUND_MAX_AGE: u32 = 60;
+
+/// The validator and the lifetime of a response about a round
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheInfo {
+    pub etag: EntityTag,
+    pub max_age: u32,
+}
+
+/// Cache info for a response of an endpoint about a round and e.g. an address.
+/// The ETag changes whenever the root of the served round changes. None if the round can't be served.
+/// Previews aren't cached, as the drafts in them can still change
+pub fn cache_info(round: Option<u8>, path: &str, keys: &[Option<&str>]) -> Option<CacheInfo> {
+    let api_data = get_api_data();
+    let rounds = served_rounds(&api_data.rounds, &api_data.config, Visibility::Served);
+    let data = select_round(&rounds, round).ok()?;
+    let latest = select_round(&rounds, None).ok()?;
+
+    // The hash is the same for every instance of the same build, which is what a cache in front of them needs
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    data.tree.root.to_bytes_be().hash(&mut hasher);
+    for key in keys.iter() {
+        // Addresses written in different ways give the same response
+        match key.map(Address::from_str) {
+            Some(Ok(address)) => address.felt().to_bytes_be().hash(&mut hasher),
+            _ => key.hash(&mut hasher),
+        }
+    }
+
+    let max_age = match round {
+        Some(_) if data.round < latest.round => PAST_ROUND_MAX_AGE,
+        _ => LATEST_ROUND_MAX_AGE,
+    };
+    Some(CacheInfo {
+        etag: EntityTag::new_strong(format!("{}-{:016x}", data.round, hasher.finish())),
+        max_age,
+    })
+}
+
+/// 304 Not Modified if the client already has the response with this ETag
+pub fn not_modified(req: &HttpRequest, cache: &Option<CacheInfo>) -> Option<HttpResponse> {
+    let cache = cache.as_ref()?;
+    let matches = match req.get_header::<IfNoneMatch>()? {
+        IfNoneMatch::Any => true,
+        IfNoneMatch::Items(tags) => tags.iter().any(|t| t.weak_eq(&cache.etag)),
+    };
+    match matches {
+        true => Some(with_cache_headers(&mut HttpResponse::NotModified(), cache).finish()),
+        false => None,
+    }
+}
+
+/// A response builder with the ETag and the Cache-Control headers, if the response can be cached
+pub fn cached(mut builder: HttpResponseBuilder, cache: &Option<CacheInfo>) -> HttpResponseBuilder {
+    if let Some(cache) = cache {
+        with_cache_headers(&mut builder, cache);
+    }
+    builder
+}
+
+fn with_cache_headers<'a>(
+    builder: &'a mut HttpResponseBuilder,
+    cache: &CacheInfo,
+) -> &'a mut HttpResponseBuilder {
+    builder
+        .insert_header(ETag(cache.etag.clone()))
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(cache.max_age),
+        ]))
+}
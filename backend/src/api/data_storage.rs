@@ -1,21 +1,94 @@
-use super::processor::read_allocations;
-use crate::api::structs::RoundTreeData;
+use super::{
+    indexer::{claims_file, read_claims},
+    lifecycle::served_rounds,
+    metrics::record_reload,
+    processor::{build_allocations, read_campaign_config},
+    vesting::read_vesting_schedules,
+};
+use crate::api::structs::{
+    CampaignConfig, ClaimIndex, RoundState, RoundTreeData, VestingSchedule, Visibility,
+};
+use arc_swap::ArcSwap;
 use lazy_static::lazy_static;
-use std::sync::{RwLock, RwLockReadGuard};
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::{info, instrument, warn};
 
-// Use RwLock to allow for mutable access to the data
+/// Folder of the input files and the campaign settings
+pub const INPUT_FOLDER: &str = "./raw_input";
+
+/// Everything the API serves. It's replaced as a whole, so a request sees the rounds,
+/// the settings, the vesting schedules and the claims of the same moment
+#[derive(Debug, Clone, Default)]
+pub struct ApiData {
+    pub rounds: Arc<Vec<Arc<RoundTreeData>>>,
+    pub config: CampaignConfig,
+    pub vesting_schedules: Vec<VestingSchedule>,
+    pub claims: ClaimIndex,
+}
+
+// Swapped atomically on reload, so requests never wait for a rebuild
 lazy_static! {
-    static ref ROUND_DATA: RwLock<Vec<RoundTreeData>> = RwLock::new(Vec::new());
+    static ref API_DATA: ArcSwap<ApiData> = ArcSwap::from_pointee(ApiData::default());
+}
+
+/// The current data. Stays valid if the data is reloaded meanwhile
+pub fn get_api_data() -> Arc<ApiData> {
+    API_DATA.load_full()
+}
+
+/// The current rounds. Stays valid if the data is reloaded meanwhile
+pub fn get_all_data() -> Arc<Vec<Arc<RoundTreeData>>> {
+    API_DATA.load().rounds.clone()
+}
+
+/// Changes a copy of the current data and swaps it in. Concurrent updates are retried, so none is lost
+pub fn update_data(update: impl Fn(&mut ApiData)) {
+    API_DATA.rcu(|data| {
+        let mut data = ApiData::clone(data);
+        update(&mut data);
+        data
+    });
+}
+
+/// Replaces the rounds served by the API
+pub fn set_round_data(rounds: Vec<RoundTreeData>) {
+    let rounds = Arc::new(rounds.into_iter().map(Arc::new).collect());
+    update_data(|data| data.rounds = Arc::clone(&rounds));
+}
+
+/// Replaces the states of the rounds, e.g. after an operator approved a round
+pub fn set_round_states(states: BTreeMap<u8, RoundState>) {
+    update_data(|data| data.config.round_states = states.clone());
 }
 
-pub fn get_all_data() -> RwLockReadGuard<'static, Vec<RoundTreeData>> {
-    ROUND_DATA.read().expect("Failed to acquire read lock")
+/// Replaces the indexed claims, e.g. after the indexer has read new events
+pub fn set_claims(claims: ClaimIndex) {
+    update_data(|data| data.claims = claims.clone());
 }
 
+#[instrument]
 pub fn update_api_data() {
-    let mut data = ROUND_DATA.write().expect("Failed to acquire write lock");
+    let config = read_campaign_config(INPUT_FOLDER);
+    let schedules = read_vesting_schedules(INPUT_FOLDER);
+    let drops = build_allocations(INPUT_FOLDER.to_string(), &config, &schedules);
+    // New files are built as drafts and aren't served until they're approved
+    let served = served_rounds(&drops, &config, Visibility::Served).len();
+    if served < drops.len() {
+        warn!(
+            rounds = drops.len(),
+            served, "Some rounds aren't approved and are only served in previews"
+        );
+    }
 
-    let drops = read_allocations("./raw_input".to_string());
+    let memory_bytes = drops.iter().map(|d| d.estimated_size()).sum();
+    let rounds = drops.len();
+    API_DATA.store(Arc::new(ApiData {
+        rounds: Arc::new(drops.into_iter().map(Arc::new).collect()),
+        config,
+        vesting_schedules: schedules,
+        claims: read_claims(&claims_file()),
+    }));
 
-    *data = drops;
+    record_reload(memory_bytes);
+    info!(rounds, memory_bytes, "Round data updated");
 }
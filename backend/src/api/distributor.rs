@@ -0,0 +1,147 @@
+use starknet_crypto::{pedersen_hash, poseidon_hash, FieldElement};
+use std::{collections::HashMap, str::FromStr};
+
+use super::{math::felt_to_u128, structs::CairoCalldata};
+
+/// A Claimed event emitted by the model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedClaim {
+    pub claimee: FieldElement,
+    pub amount: u128,
+}
+
+/// A model of the Distributor contract, to test rollouts of rounds without deploying.
+/// Follows contract.cairo: failed calls return the contract's panic message and change nothing, like a reverted transaction
+#[derive(Debug, Clone)]
+pub struct Distributor {
+    owner: FieldElement,
+    /// The roots in the order they are added. Slot i holds the i-th root
+    merkle_roots: Vec<FieldElement>,
+    /// The cumulative amount each address has claimed
+    allocation_claimed: HashMap<FieldElement, u128>,
+    /// The STRK held by the contract
+    balance: u128,
+    /// The STRK transferred to each address
+    balances: HashMap<FieldElement, u128>,
+    events: Vec<SimulatedClaim>,
+}
+
+impl Distributor {
+    pub fn new(owner: FieldElement) -> Self {
+        Distributor {
+            owner,
+            merkle_roots: vec![],
+            allocation_claimed: HashMap::new(),
+            balance: 0,
+            balances: HashMap::new(),
+            events: vec![],
+        }
+    }
+
+    /// Transfers STRK to the contract
+    pub fn fund(&mut self, amount: u128) {
+        self.balance += amount;
+    }
+
+    pub fn add_root(&mut self, caller: FieldElement, new_root: FieldElement) -> Result<(), String> {
+        if caller != self.owner {
+            return Err("Caller is not the owner".to_string());
+        }
+        // The contract looks for the first free slot, i.e. the first slot holding 0
+        if new_root != FieldElement::ZERO {
+            self.merkle_roots.push(new_root);
+        }
+        Ok(())
+    }
+
+    pub fn roots(&self) -> &[FieldElement] {
+        &self.merkle_roots
+    }
+
+    /// Computes the root from the poseidon(claimee, amount) leaf and the proof with sorted-pair pedersen hashes
+    pub fn get_root_for(
+        &self,
+        claimee: FieldElement,
+        amount: u128,
+        proof: &[FieldElement],
+    ) -> FieldElement {
+        let leaf = poseidon_hash(claimee, FieldElement::from(amount));
+        proof.iter().fold(leaf, |node, proof_element| {
+            // Compared as u256 like in the contract
+            if node.to_bytes_be() < proof_element.to_bytes_be() {
+                pedersen_hash(&node, proof_element)
+            } else {
+                pedersen_hash(proof_element, &node)
+            }
+        })
+    }
+
+    /// Claims the difference between the amount and what the caller has claimed so far. Returns the amount transferred
+    pub fn claim(
+        &mut self,
+        caller: FieldElement,
+        amount: u128,
+        proof: &[FieldElement],
+    ) -> Result<u128, String> {
+        let root = self.get_root_for(caller, amount, proof);
+
+        // The contract reads roots.at(0) before checking the length
+        if self.merkle_roots.is_empty() {
+            return Err("Index out of bounds".to_string());
+        }
+        if !self.merkle_roots.contains(&root) {
+            return Err("INVALID PROOF".to_string());
+        }
+
+        let left_to_claim = amount
+            .checked_sub(self.amount_already_claimed(caller))
+            .ok_or("u128_sub Overflow".to_string())?;
+        if left_to_claim > self.balance {
+            return Err("ERC20: insufficient balance".to_string());
+        }
+
+        self.balance -= left_to_claim;
+        *self.balances.entry(caller).or_insert(0) += left_to_claim;
+        self.allocation_claimed.insert(caller, amount);
+        self.events.push(SimulatedClaim {
+            claimee: caller,
+            amount,
+        });
+        Ok(left_to_claim)
+    }
+
+    /// Claims with the calldata given by the backend
+    pub fn claim_with_calldata(
+        &mut self,
+        caller: FieldElement,
+        calldata: &CairoCalldata,
+    ) -> Result<u128, String> {
+        let amount = FieldElement::from_str(&calldata.amount).map_err(|e| e.to_string())?;
+        let amount = felt_to_u128(&amount).ok_or("Amount is out of range".to_string())?;
+        let proof = calldata
+            .proof
+            .iter()
+            .map(|p| FieldElement::from_str(p).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<FieldElement>, String>>()?;
+        self.claim(caller, amount, &proof)
+    }
+
+    pub fn amount_already_claimed(&self, claimee: FieldElement) -> u128 {
+        self.allocation_claimed.get(&claimee).copied().unwrap_or(0)
+    }
+
+    /// The STRK held by the contract
+    pub fn contract_balance(&self) -> u128 {
+        self.balance
+    }
+
+    /// The STRK transferred to an address
+    pub fn balance_of(&self, account: FieldElement) -> u128 {
+        self.balances.get(&account).copied().unwrap_or(0)
+    }
+
+    /// The Claimed events emitted so far
+    pub fn events(&self) -> &[SimulatedClaim] {
+        &self.events
+    }
+}
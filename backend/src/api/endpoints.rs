@@ -1,10 +1,15 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 
 use super::{
-    processor::{get_raw_allocation_amount, get_raw_calldata, get_raw_root}, structs::{CairoCalldata, RootQueryResult},
+    admin::authorize_preview,
+    caching::{cache_info, cached, not_modified},
+    logging::address_hash,
+    metrics::{gather_metrics, record_error},
+    processor::{get_raw_allocation_amount, get_raw_allocation_claims, get_raw_calldata, get_raw_multiproof_calldata, get_raw_publication_plan, get_raw_root, get_raw_rounds, get_raw_vesting_schedule}, structs::{AllocationClaimsResult, CairoCalldata, CairoMultiproofCalldata, ExclusionResult, LimitKind, LimitResult, PublicationPlan, PublicationStatus, PublicationStep, RootQueryResult, RoundState, RoundSummaryResult, TokenTotalsResult, Visibility, VestingRound},
 };
 use actix_web::get;
 use serde::Deserialize;
+use tracing::{info, instrument, warn};
 use utoipa::{IntoParams, OpenApi};
 
 #[derive(OpenApi)]
@@ -12,10 +17,15 @@ use utoipa::{IntoParams, OpenApi};
     paths(
         get_root,
         get_allocation_amount,
-        get_calldata
+        get_calldata,
+        get_multiproof_calldata,
+        get_vesting_schedule,
+        get_publication_plan,
+        get_rounds,
+        get_metrics
     ),
     components(
-        schemas(CairoCalldata, RootQueryResult)
+        schemas(AllocationClaimsResult, CairoCalldata, CairoMultiproofCalldata, ExclusionResult, LimitKind, LimitResult, PublicationPlan, PublicationStatus, PublicationStep, RootQueryResult, RoundState, RoundSummaryResult, TokenTotalsResult, VestingRound)
     ),
     tags(
         (name = "DeFi Incentives REST API", description = "DeFi incentives allocation endpoints")
@@ -29,7 +39,11 @@ pub struct GetCalldataParams {
     /// Which round to query for. Leave out or 0 for the latest round.
     round: Option<u8>,
     /// Which address to query for.
-    address: String
+    address: String,
+    /// Which token to query for. Leave out for the allocation without a token, the one the Distributor pays out.
+    token: Option<String>,
+    /// Also serve drafts and validated rounds. Needs an admin API key.
+    preview: Option<bool>
 }
 
 #[utoipa::path(
@@ -42,15 +56,86 @@ pub struct GetCalldataParams {
     ),    
 )]
 #[get("/get_calldata")]
-pub async fn get_calldata(query: web::Query<GetCalldataParams>) -> impl Responder {
+#[instrument(skip_all, fields(round = query.round, address_hash = %address_hash(&query.address), token = ?query.token))]
+pub async fn get_calldata(req: HttpRequest, query: web::Query<GetCalldataParams>) -> impl Responder {
     // Get the round parameter. Use the max found round if it's not given in query parameters or is 0
     let round = if query.round == Some(0) { None } else { query.round };
+    let visibility = match authorize_preview(&req, query.preview, round) {
+        Ok(v) => v,
+        Err(response) => return *response,
+    };
+
+    let cache = if visibility == Visibility::Preview { None } else { cache_info(round, req.path(), &[Some(&query.address), query.token.as_deref()]) };
+    if let Some(response) = not_modified(&req, &cache) {
+        return response;
+    }
 
-    let calldata = get_raw_calldata(round, &query.address);
+    let calldata = get_raw_calldata(round, visibility, &query.address, query.token.as_deref());
 
     match calldata {
-        Ok(value) => HttpResponse::Ok().json(value),
-        Err(value) => HttpResponse::BadRequest().json(value)
+        Ok(value) => {
+            info!("Calldata generated");
+            cached(HttpResponse::Ok(), &cache).json(value)
+        }
+        Err(value) => {
+            warn!(error = %value, "Request failed");
+            record_error(&value);
+            HttpResponse::BadRequest().json(&value.message)
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct GetMultiproofCalldataParams {
+    /// Which round to query for. Leave out or 0 for the latest round.
+    round: Option<u8>,
+    /// Which addresses to query for, separated by commas.
+    addresses: String,
+    /// Also serve drafts and validated rounds. Needs an admin API key.
+    preview: Option<bool>
+}
+
+#[utoipa::path(
+    tag = "Generates calldata with one combined proof for several addresses",
+    responses(
+        (status = 200, description= "Leaves, proof and proof flags for the addresses", body = CairoMultiproofCalldata),
+    ),
+    params(
+        GetMultiproofCalldataParams
+    ),
+)]
+#[get("/get_multiproof_calldata")]
+#[instrument(skip_all, fields(round = query.round))]
+pub async fn get_multiproof_calldata(req: HttpRequest, query: web::Query<GetMultiproofCalldataParams>) -> impl Responder {
+    // Get the round parameter. Use the max found round if it's not given in query parameters or is 0
+    let round = if query.round == Some(0) { None } else { query.round };
+    let visibility = match authorize_preview(&req, query.preview, round) {
+        Ok(v) => v,
+        Err(response) => return *response,
+    };
+
+    let cache = if visibility == Visibility::Preview { None } else { cache_info(round, req.path(), &[Some(&query.addresses)]) };
+    if let Some(response) = not_modified(&req, &cache) {
+        return response;
+    }
+
+    let addresses: Vec<String> = query
+        .addresses
+        .split(',')
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect();
+
+    match get_raw_multiproof_calldata(round, visibility, &addresses) {
+        Ok(value) => {
+            info!(leaves = value.addresses.len(), "Multiproof calldata generated");
+            cached(HttpResponse::Ok(), &cache).json(value)
+        }
+        Err(value) => {
+            warn!(error = %value, "Request failed");
+            record_error(&value);
+            HttpResponse::BadRequest().json(&value.message)
+        }
     }
 }
 
@@ -59,26 +144,64 @@ pub struct GetAllocationAmountParams {
     /// Which round to query for. Leave out or 0 for the latest round.
     round: Option<u8>,
     /// Which address to query for.
-    address: String
+    address: String,
+    /// Which token to query for. Leave out for the allocation without a token, the one the Distributor pays out.
+    token: Option<String>,
+    /// Also return the claimed amount and the amount claimable now. The result is then an object instead of the amount.
+    include_claims: Option<bool>,
+    /// Also serve drafts and validated rounds. Needs an admin API key.
+    preview: Option<bool>
 }
 
 #[utoipa::path(
     tag = "Gets the allocated, accumulated amount for a given address",
     responses(
-        (status = 200, description= "The allocated amount", body = String),       
+        (status = 200, description= "The allocated amount, or the amount with the claims if include_claims is given", body = String),       
     ),
     params(
         GetAllocationAmountParams
     ),    
 )]
 #[get("/get_allocation_amount")]
-pub async fn get_allocation_amount(query: web::Query<GetAllocationAmountParams>) -> impl Responder {
+#[instrument(skip_all, fields(round = query.round, address_hash = %address_hash(&query.address), token = ?query.token))]
+pub async fn get_allocation_amount(req: HttpRequest, query: web::Query<GetAllocationAmountParams>) -> impl Responder {
     // Get the round parameter. Use the max found round if it's not given in query parameters or is 0
     let round = if query.round == Some(0) { None } else { query.round };
-    
-    match get_raw_allocation_amount(round, &query.address) {
-        Ok(value) => HttpResponse::Ok().json(value.to_string()),
-        Err(value) => HttpResponse::BadRequest().json(value)
+    let visibility = match authorize_preview(&req, query.preview, round) {
+        Ok(v) => v,
+        Err(response) => return *response,
+    };
+
+    if query.include_claims == Some(true) {
+        return match get_raw_allocation_claims(round, visibility, &query.address, query.token.as_deref()) {
+            Ok(value) => {
+                info!(amount = %value.amount, claimed = %value.claimed, "Allocation amount and claims retrieved");
+                HttpResponse::Ok().json(value)
+            }
+            Err(value) => {
+                warn!(error = %value, "Request failed");
+                record_error(&value);
+                HttpResponse::BadRequest().json(&value.message)
+            }
+        };
+    }
+
+    // The claims change without a new round, so only the amount alone is cached
+    let cache = if visibility == Visibility::Preview { None } else { cache_info(round, req.path(), &[Some(&query.address), query.token.as_deref()]) };
+    if let Some(response) = not_modified(&req, &cache) {
+        return response;
+    }
+
+    match get_raw_allocation_amount(round, visibility, &query.address, query.token.as_deref()) {
+        Ok(value) => {
+            info!(amount = %value, "Allocation amount retrieved");
+            cached(HttpResponse::Ok(), &cache).json(value.to_string())
+        }
+        Err(value) => {
+            warn!(error = %value, "Request failed");
+            record_error(&value);
+            HttpResponse::BadRequest().json(&value.message)
+        }
     }
 }
 
@@ -86,6 +209,8 @@ pub async fn get_allocation_amount(query: web::Query<GetAllocationAmountParams>)
 pub struct GetRootParams {
     /// Which round to query for. Leave out or 0 for the latest round.
     round: Option<u8>,
+    /// Also serve drafts and validated rounds. Needs an admin API key.
+    preview: Option<bool>,
 }
 
 #[utoipa::path(
@@ -98,12 +223,101 @@ pub struct GetRootParams {
     ),    
 )]
 #[get("/get_root")]
-pub async fn get_root(query: web::Query<GetRootParams>) -> impl Responder {
+#[instrument(skip_all, fields(round = query.round))]
+pub async fn get_root(req: HttpRequest, query: web::Query<GetRootParams>) -> impl Responder {
     // Get the round parameter. Use the max found round if it's not given in query parameters or is 0
     let round = if query.round == Some(0) { None } else { query.round };
+    let visibility = match authorize_preview(&req, query.preview, round) {
+        Ok(v) => v,
+        Err(response) => return *response,
+    };
+
+    let cache = if visibility == Visibility::Preview { None } else { cache_info(round, req.path(), &[]) };
+    if let Some(response) = not_modified(&req, &cache) {
+        return response;
+    }
 
-    match get_raw_root(round)  {
-        Ok(v) => HttpResponse::Ok().json(&v),
-        Err(value) => return HttpResponse::BadRequest().json(value),
+    match get_raw_root(round, visibility)  {
+        Ok(v) => {
+            info!(root = %v.root, "Root retrieved");
+            cached(HttpResponse::Ok(), &cache).json(&v)
+        }
+        Err(value) => {
+            warn!(error = %value, "Request failed");
+            record_error(&value);
+            HttpResponse::BadRequest().json(&value.message)
+        }
     }
 }
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct GetVestingScheduleParams {
+    /// Which address to query for.
+    address: String
+}
+
+#[utoipa::path(
+    tag = "Previews the vesting schedules of a given address, including rounds that aren't published yet",
+    responses(
+        (status = 200, description= "The amount released in each round of the schedules", body = [VestingRound]),
+    ),
+    params(
+        GetVestingScheduleParams
+    ),
+)]
+#[get("/get_vesting_schedule")]
+#[instrument(skip_all, fields(address_hash = %address_hash(&query.address)))]
+pub async fn get_vesting_schedule(query: web::Query<GetVestingScheduleParams>) -> impl Responder {
+    match get_raw_vesting_schedule(&query.address) {
+        Ok(value) => {
+            info!(rounds = value.len(), "Vesting schedule retrieved");
+            HttpResponse::Ok().json(value)
+        }
+        Err(value) => {
+            warn!(error = %value, "Request failed");
+            record_error(&value);
+            HttpResponse::BadRequest().json(&value.message)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Lists the roots that still have to be added to the contract, in slot order",
+    responses(
+        (status = 200, description= "The slot of each round and whether its root is already published", body = PublicationPlan),
+    ),
+)]
+#[get("/publication_plan")]
+#[instrument]
+pub async fn get_publication_plan() -> impl Responder {
+    let plan = get_raw_publication_plan();
+    info!(steps = plan.steps.len(), missing_rounds = plan.missing_rounds.len(), "Publication plan generated");
+    HttpResponse::Ok().json(plan)
+}
+
+#[utoipa::path(
+    tag = "Lists all rounds with their totals and how much of them is claimed",
+    responses(
+        (status = 200, description= "The rounds in round order", body = [RoundSummaryResult]),
+    ),
+)]
+#[get("/rounds")]
+#[instrument]
+pub async fn get_rounds() -> impl Responder {
+    let rounds = get_raw_rounds();
+    info!(rounds = rounds.len(), "Rounds retrieved");
+    HttpResponse::Ok().json(rounds)
+}
+
+#[utoipa::path(
+    tag = "Metrics of the backend in the Prometheus text format",
+    responses(
+        (status = 200, description= "Request, error, tree building and memory metrics", body = String),
+    ),
+)]
+#[get("/metrics")]
+pub async fn get_metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(gather_metrics())
+}
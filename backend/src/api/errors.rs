@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// What kind of error a request failed with. The error metric is labelled with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// There's no data for the round, or no round at all
+    RoundNotFound,
+    /// The address, or the address with the token, has no leaf in the round
+    AddressNotFound,
+    /// The round has no slot yet because an earlier round is missing
+    RoundNotSequenced,
+    /// The round is retired, or isn't approved and the request isn't a preview
+    RoundNotServed,
+    /// The address or token of the query can't be parsed
+    InvalidAddress,
+    Other,
+}
+
+/// An error returned by the API: the message for the response and its kind for the metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        ApiError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
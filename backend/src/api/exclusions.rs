@@ -0,0 +1,143 @@
+use serde::Deserialize;
+use serde_json::from_slice;
+use starknet_crypto::FieldElement;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+use tracing::info;
+
+use super::{
+    address::{format_address, Address},
+    math::pro_rata,
+    structs::{AllocationKey, DenylistEntry, ExclusionRecord},
+};
+
+/// What happens to the amounts of denied addresses
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionPolicy {
+    /// The amounts are removed from the round
+    #[default]
+    Drop,
+    /// The amounts are shared among the other addresses of the round, pro rata to their amounts in the round
+    Redistribute,
+}
+
+/// Denylist entries by address
+pub struct Denylist<'a> {
+    entries: HashMap<FieldElement, &'a DenylistEntry>,
+}
+
+impl<'a> Denylist<'a> {
+    pub fn new(entries: &'a [DenylistEntry]) -> Self {
+        let entries = entries.iter().map(|e| (e.felt, e)).collect();
+        Denylist { entries }
+    }
+
+    /// The entry denying the address in the given round, if there is one
+    pub fn denies(&self, address: &FieldElement, round: u8) -> Option<&'a DenylistEntry> {
+        self.entries
+            .get(address)
+            .filter(|e| !matches!(e.from_round, Some(from) if round < from))
+            .copied()
+    }
+}
+
+/// Handles the amounts removed from a round according to the policy and records each of them.
+/// Amounts are shared per token, and dropped if the round has no other allocations in the token
+pub fn apply_exclusions(
+    round: u8,
+    round_amounts: &mut HashMap<AllocationKey, u128>,
+    excluded: &HashMap<AllocationKey, u128>,
+    denylist: &Denylist,
+    policy: ExclusionPolicy,
+) -> Vec<ExclusionRecord> {
+    let mut redistributed_tokens: HashSet<Option<FieldElement>> = HashSet::new();
+    if policy == ExclusionPolicy::Redistribute {
+        let mut tokens: Vec<Option<FieldElement>> = excluded.keys().map(|k| k.1).collect();
+        tokens.sort();
+        tokens.dedup();
+        for token in tokens {
+            let total: u128 = excluded
+                .iter()
+                .filter(|(key, _)| key.1 == token)
+                .map(|(_, amount)| amount)
+                .sum();
+            if redistribute(round_amounts, token, total) {
+                redistributed_tokens.insert(token);
+            }
+        }
+    }
+
+    let mut records: Vec<ExclusionRecord> = excluded
+        .iter()
+        .map(|(key, amount)| {
+            let (address, token) = *key;
+            ExclusionRecord {
+                round,
+                address,
+                token,
+                amount: *amount,
+                reason: denylist
+                    .denies(&address, round)
+                    .map(|e| e.reason.clone())
+                    .unwrap_or_default(),
+                redistributed: redistributed_tokens.contains(&token),
+            }
+        })
+        .collect();
+    records.sort_by_key(|r| (r.address, r.token));
+
+    for record in records.iter() {
+        info!(
+            round,
+            address = %format_address(&record.address),
+            token = ?record.token.as_ref().map(format_address),
+            amount = %record.amount,
+            reason = %record.reason,
+            redistributed = record.redistributed,
+            "Excluded allocation"
+        );
+    }
+    records
+}
+
+/// Shares an amount among the allocations of a token, pro rata to their amounts.
+/// The rounding dust goes to the largest allocation. Returns false if there are no allocations to share with
+fn redistribute(
+    round_amounts: &mut HashMap<AllocationKey, u128>,
+    token: Option<FieldElement>,
+    total: u128,
+) -> bool {
+    let recipients: Vec<(AllocationKey, u128)> = round_amounts
+        .iter()
+        .filter(|(key, amount)| key.1 == token && **amount > 0)
+        .map(|(key, amount)| (*key, *amount))
+        .collect();
+    let shares = pro_rata(total, &recipients);
+    for (key, share) in shares.iter() {
+        *round_amounts.get_mut(key).unwrap() += share;
+    }
+    !shares.is_empty()
+}
+
+/// Reads the denylist from denylist.json in the input folder. Empty if there's no such file
+pub fn read_denylist(filepath: &str) -> Vec<DenylistEntry> {
+    let path = Path::new(filepath).join("denylist.json");
+    if !path.exists() {
+        return Vec::new();
+    }
+    let contents = fs::read(&path).expect("Failed to read denylist");
+    let mut denylist: Vec<DenylistEntry> =
+        from_slice(&contents).expect("Failed to deserialize denylist");
+    for (index, entry) in denylist.iter_mut().enumerate() {
+        entry.felt = Address::from_str(&entry.address)
+            .unwrap_or_else(|e| panic!("Invalid address in denylist entry {}: {}", index, e))
+            .felt();
+    }
+    info!(addresses = denylist.len(), "Read denylist");
+    denylist
+}
@@ -0,0 +1,282 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde_json::to_vec_pretty;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    str::FromStr,
+};
+use tracing::info;
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipWriter};
+
+use super::structs::{JSONAllocation, RoundAmounts};
+
+const GENERATE_USAGE: &str = "Usage: api_run generate <folder> [--seed N] [--rounds N] [--addresses N] \
+[--overlap 0-1] [--distribution uniform|pareto] [--min-amount N] [--max-amount N] [--malformed 0-1]";
+
+/// Shape of the Pareto distribution where 20% of the addresses get 80% of the amounts
+const PARETO_SHAPE: f64 = 1.16;
+
+/// How the generated amounts are distributed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AmountDistribution {
+    /// Every amount between the minimum and the maximum is equally likely
+    #[default]
+    Uniform,
+    /// Most amounts are close to the minimum and a few are large, like real rewards. Capped at the maximum
+    Pareto,
+}
+
+impl FromStr for AmountDistribution {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "uniform" => Ok(AmountDistribution::Uniform),
+            "pareto" => Ok(AmountDistribution::Pareto),
+            _ => Err(format!("Unknown distribution {}", value)),
+        }
+    }
+}
+
+/// Settings of a generated dataset
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// The same seed and settings always give the same files
+    pub seed: u64,
+    pub rounds: u8,
+    /// Rows per round
+    pub addresses: u64,
+    /// Share of the addresses of a round that were also in the previous round, from 0 to 1
+    pub overlap: f64,
+    pub distribution: AmountDistribution,
+    pub min_amount: u128,
+    pub max_amount: u128,
+    /// Share of the rows with an invalid address or amount, from 0 to 1
+    pub malformed: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            seed: 0,
+            rounds: 1,
+            addresses: 1000,
+            overlap: 0.5,
+            distribution: AmountDistribution::Uniform,
+            // 1 to 1000 STRK
+            min_amount: 1_000_000_000_000_000_000,
+            max_amount: 1_000_000_000_000_000_000_000,
+            malformed: 0.0,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rounds == 0 {
+            return Err("Rounds must be between 1 and 255".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.overlap) {
+            return Err("Overlap must be between 0 and 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.malformed) {
+            return Err("Malformed share must be between 0 and 1".to_string());
+        }
+        if self.min_amount > self.max_amount {
+            return Err("Minimum amount is larger than the maximum amount".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Synthetic allocations like the ones of frontend/src/data-generator.ts.
+/// The addresses are 1 to count padded to 64 hex digits, and address i gets 1000 + 10 * i
+pub fn generate_allocations(count: u64) -> Vec<JSONAllocation> {
+    (1..=count)
+        .map(|i| JSONAllocation {
+            address: format!("{:#066x}", i),
+            amount: (1000 + i as u128 * 10).to_string(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Generates the rows of rounds 1 to config.rounds from random addresses and amounts
+pub fn generate_rounds(config: &GeneratorConfig) -> Result<Vec<RoundAmounts>, String> {
+    config.validate()?;
+    let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+    let mut rounds: Vec<RoundAmounts> = vec![];
+    let mut previous: Vec<String> = vec![];
+
+    for round in 1..=config.rounds {
+        let kept =
+            ((config.overlap * config.addresses as f64).round() as usize).min(previous.len());
+        let mut addresses: Vec<String> =
+            previous.choose_multiple(&mut rng, kept).cloned().collect();
+        while (addresses.len() as u64) < config.addresses {
+            addresses.push(random_address(&mut rng));
+        }
+        addresses.shuffle(&mut rng);
+
+        let mut malformed = 0;
+        let amounts = addresses
+            .iter()
+            .map(|address| {
+                let row = JSONAllocation {
+                    address: address.clone(),
+                    amount: random_amount(&mut rng, config).to_string(),
+                    ..Default::default()
+                };
+                match rng.gen_bool(config.malformed) {
+                    true => {
+                        malformed += 1;
+                        malformed_row(&mut rng, row)
+                    }
+                    false => row,
+                }
+            })
+            .collect();
+        info!(
+            round,
+            rows = addresses.len(),
+            kept,
+            malformed,
+            "Generated round"
+        );
+
+        rounds.push(RoundAmounts { round, amounts });
+        previous = addresses;
+    }
+    Ok(rounds)
+}
+
+/// A random address below 2^251, like the contract addresses of Starknet
+fn random_address(rng: &mut ChaCha8Rng) -> String {
+    let mut bytes = [0_u8; 32];
+    rng.fill(&mut bytes);
+    bytes[0] &= 0x07;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+fn random_amount(rng: &mut ChaCha8Rng, config: &GeneratorConfig) -> u128 {
+    match config.distribution {
+        AmountDistribution::Uniform => rng.gen_range(config.min_amount..=config.max_amount),
+        AmountDistribution::Pareto => {
+            // Inverse of the distribution function, with u in (0, 1]
+            let u = 1.0 - rng.gen::<f64>();
+            let amount = config.min_amount as f64 / u.powf(1.0 / PARETO_SHAPE);
+            (amount as u128).clamp(config.min_amount, config.max_amount)
+        }
+    }
+}
+
+/// Replaces the address or the amount of a row with a value the backend can't use
+fn malformed_row(rng: &mut ChaCha8Rng, row: JSONAllocation) -> JSONAllocation {
+    match rng.gen_range(0..6) {
+        0 => JSONAllocation {
+            address: "not_an_address".to_string(),
+            ..row
+        },
+        // Larger than the field prime
+        1 => JSONAllocation {
+            address: format!("0x{}", "f".repeat(64)),
+            ..row
+        },
+        2 => JSONAllocation {
+            address: String::new(),
+            ..row
+        },
+        3 => JSONAllocation {
+            amount: "not_an_amount".to_string(),
+            ..row
+        },
+        4 => JSONAllocation {
+            amount: format!("-{}", row.amount),
+            ..row
+        },
+        // u128::MAX + 1
+        _ => JSONAllocation {
+            amount: "340282366920938463463374607431768211456".to_string(),
+            ..row
+        },
+    }
+}
+
+/// Writes the allocations to raw_<round>.zip in the folder, as raw_<round>.json inside the archive.
+/// The timestamps are fixed, so the same allocations always give the same file
+pub fn write_round_file(
+    folder: &Path,
+    round: u8,
+    allocations: &[JSONAllocation],
+) -> Result<(), String> {
+    let contents = to_vec_pretty(allocations).map_err(|e| e.to_string())?;
+    let file =
+        File::create(folder.join(format!("raw_{}.zip", round))).map_err(|e| e.to_string())?;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default());
+    let mut zip = ZipWriter::new(file);
+    zip.start_file(format!("raw_{}.json", round), options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(&contents).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Writes rounds 1 to rounds into the folder, each with the same allocations of generate_allocations
+pub fn write_fixture(folder: &Path, rounds: u8, count: u64) -> Result<(), String> {
+    fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    let allocations = generate_allocations(count);
+    for round in 1..=rounds {
+        write_round_file(folder, round, &allocations)?;
+    }
+    info!(rounds, addresses = count, folder = %folder.display(), "Generated input files");
+    Ok(())
+}
+
+/// Writes the rounds of generate_rounds into the folder
+pub fn write_rounds(folder: &Path, config: &GeneratorConfig) -> Result<(), String> {
+    let rounds = generate_rounds(config)?;
+    fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    for round in rounds.iter() {
+        write_round_file(folder, round.round, &round.amounts)?;
+    }
+    info!(rounds = config.rounds, addresses = config.addresses, seed = config.seed, folder = %folder.display(), "Generated input files");
+    Ok(())
+}
+
+/// Runs the generate subcommand with the arguments after "generate"
+pub fn run_generate(args: &[String]) -> Result<(), String> {
+    let mut args = args.iter();
+    let folder = match args.next() {
+        Some(v) if !v.starts_with("--") => v,
+        _ => return Err(GENERATE_USAGE.to_string()),
+    };
+
+    let mut config = GeneratorConfig::default();
+    while let Some(option) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {}", option))?;
+        match option.as_str() {
+            "--seed" => config.seed = parse_option(option, value)?,
+            "--rounds" => config.rounds = parse_option(option, value)?,
+            "--addresses" => config.addresses = parse_option(option, value)?,
+            "--overlap" => config.overlap = parse_option(option, value)?,
+            "--distribution" => config.distribution = parse_option(option, value)?,
+            "--min-amount" => config.min_amount = parse_option(option, value)?,
+            "--max-amount" => config.max_amount = parse_option(option, value)?,
+            "--malformed" => config.malformed = parse_option(option, value)?,
+            _ => return Err(format!("Unknown option {}\n{}", option, GENERATE_USAGE)),
+        }
+    }
+    write_rounds(Path::new(folder), &config)
+}
+
+fn parse_option<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
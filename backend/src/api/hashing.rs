@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use starknet_crypto::{
+    pedersen_hash, poseidon_hash, poseidon_hash_many, poseidon_hash_single, FieldElement,
+};
+use std::fmt::Debug;
+
+use super::structs::CumulativeAllocation;
+
+/// Defines how the leaves and the nodes of a Merkle tree are hashed
+pub trait TreeHasher: Debug + Clone + Send + Sync {
+    /// Hashes the data of one allocation into a leaf
+    fn hash_leaf(&self, allocation: &CumulativeAllocation) -> FieldElement;
+
+    /// Hashes two nodes into their parent. Has to give the same result regardless of the order of the nodes
+    fn hash_node(&self, a: &FieldElement, b: &FieldElement) -> FieldElement;
+}
+
+/// The scheme used by the Distributor contract: poseidon(address, amount) leaves and sorted-pair pedersen nodes.
+/// Leaves with a token or extra fields are hashed with poseidon over
+/// [address, amount, has token, token if any, number of extra fields, extra fields...]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StandardHasher;
+
+impl TreeHasher for StandardHasher {
+    fn hash_leaf(&self, allocation: &CumulativeAllocation) -> FieldElement {
+        poseidon_leaf(allocation)
+    }
+
+    fn hash_node(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        let (a, b) = sorted(a, b);
+        pedersen_hash(a, b)
+    }
+}
+
+/// Like the standard scheme, but nodes are hashed with poseidon
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoseidonNodeHasher;
+
+impl TreeHasher for PoseidonNodeHasher {
+    fn hash_leaf(&self, allocation: &CumulativeAllocation) -> FieldElement {
+        poseidon_leaf(allocation)
+    }
+
+    fn hash_node(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        let (a, b) = sorted(a, b);
+        poseidon_hash(*a, *b)
+    }
+}
+
+/// OpenZeppelin style leaves, hashed twice: poseidon(poseidon(address, amount)).
+/// A leaf can't then be confused with a node, which prevents second-preimage attacks.
+/// Nodes are hashed like in the standard scheme
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DoubleHashedLeafHasher;
+
+impl TreeHasher for DoubleHashedLeafHasher {
+    fn hash_leaf(&self, allocation: &CumulativeAllocation) -> FieldElement {
+        poseidon_hash_single(poseidon_leaf(allocation))
+    }
+
+    fn hash_node(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        StandardHasher.hash_node(a, b)
+    }
+}
+
+/// The hash scheme of a campaign. Selected in the campaign config
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HashScheme {
+    #[default]
+    Standard,
+    PoseidonNode,
+    DoubleHashedLeaf,
+}
+
+impl TreeHasher for HashScheme {
+    fn hash_leaf(&self, allocation: &CumulativeAllocation) -> FieldElement {
+        match self {
+            HashScheme::Standard => StandardHasher.hash_leaf(allocation),
+            HashScheme::PoseidonNode => PoseidonNodeHasher.hash_leaf(allocation),
+            HashScheme::DoubleHashedLeaf => DoubleHashedLeafHasher.hash_leaf(allocation),
+        }
+    }
+
+    fn hash_node(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        match self {
+            HashScheme::Standard => StandardHasher.hash_node(a, b),
+            HashScheme::PoseidonNode => PoseidonNodeHasher.hash_node(a, b),
+            HashScheme::DoubleHashedLeaf => DoubleHashedLeafHasher.hash_node(a, b),
+        }
+    }
+}
+
+/// Hashes the leaf values: address, amount, the token if any and then any extra fields.
+/// The flag and the count keep a token from hashing like an extra field
+fn poseidon_leaf(allocation: &CumulativeAllocation) -> FieldElement {
+    let cumulated_amount = FieldElement::from(allocation.cumulative_amount);
+    if allocation.token.is_none() && allocation.extra_fields.is_empty() {
+        // keep order address, amount
+        return poseidon_hash(allocation.address, cumulated_amount);
+    }
+    // Same as poseidon_hash_span over [address, amount, has token, token?, n, extra fields...] in Cairo
+    let mut values = vec![allocation.address, cumulated_amount];
+    match allocation.token {
+        Some(token) => values.extend([FieldElement::ONE, token]),
+        None => values.push(FieldElement::ZERO),
+    }
+    values.push(FieldElement::from(allocation.extra_fields.len()));
+    values.extend(allocation.extra_fields.iter());
+    poseidon_hash_many(&values)
+}
+
+fn sorted<'a>(a: &'a FieldElement, b: &'a FieldElement) -> (&'a FieldElement, &'a FieldElement) {
+    if a.lt(b) {
+        return (a, b);
+    }
+    (b, a)
+}
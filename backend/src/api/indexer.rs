@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use starknet_crypto::FieldElement;
+use std::{collections::HashSet, env, fs, path::Path, str::FromStr, thread, time::Duration};
+use tracing::{info, warn};
+
+use super::{
+    address::format_address,
+    data_storage::{get_api_data, set_claims},
+    math::felt_to_u128,
+    merkle_tree::felt_to_b16,
+    structs::{ClaimIndex, ClaimedEvent, RoundTreeData},
+};
+
+/// Key of the Claimed event, sn_keccak("Claimed")
+pub const CLAIMED_SELECTOR: &str =
+    "0x035cc0235f835cc84da50813dc84eb10a75e24a21d74d6d86278c0f037cb7429";
+/// How many events are requested at once
+const CHUNK_SIZE: u64 = 1000;
+
+/// An event as returned by starknet_getEvents
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmittedEvent {
+    pub from_address: String,
+    pub keys: Vec<String>,
+    pub data: Vec<String>,
+    /// Empty for events of pending blocks
+    pub block_number: Option<u64>,
+    pub transaction_hash: String,
+}
+
+/// One page of starknet_getEvents results
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EventsPage {
+    pub events: Vec<EmittedEvent>,
+    /// Given if there are more events. Used to request the next page
+    pub continuation_token: Option<String>,
+}
+
+/// Where the Claimed events are read from
+pub trait EventSource {
+    /// Returns one page of the Claimed events of the contract from the given block on
+    fn get_events(
+        &self,
+        contract: &FieldElement,
+        from_block: u64,
+        continuation_token: Option<&str>,
+    ) -> Result<EventsPage, String>;
+}
+
+/// Reads the events from a Starknet JSON-RPC endpoint, e.g. a node or a local devnet
+pub struct RpcEventSource {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcEventSource {
+    pub fn new(url: &str) -> Self {
+        RpcEventSource {
+            url: url.to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    result: Option<EventsPage>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl EventSource for RpcEventSource {
+    fn get_events(
+        &self,
+        contract: &FieldElement,
+        from_block: u64,
+        continuation_token: Option<&str>,
+    ) -> Result<EventsPage, String> {
+        let mut filter = json!({
+            "from_block": { "block_number": from_block },
+            "to_block": "latest",
+            "address": felt_to_b16(contract),
+            "keys": [[CLAIMED_SELECTOR]],
+            "chunk_size": CHUNK_SIZE,
+        });
+        if let Some(token) = continuation_token {
+            filter["continuation_token"] = Value::from(token);
+        }
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "starknet_getEvents",
+            "params": { "filter": filter },
+        });
+
+        let response: RpcResponse = self
+            .agent
+            .post(&self.url)
+            .send_json(request)
+            .map_err(|e| e.to_string())?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        match (response.result, response.error) {
+            (Some(page), _) => Ok(page),
+            (None, Some(error)) => Err(format!("RPC error {}: {}", error.code, error.message)),
+            (None, None) => Err("RPC response has no result".to_string()),
+        }
+    }
+}
+
+/// Serves recorded starknet_getEvents results instead of a node, e.g. in tests.
+/// The continuation tokens are the indexes of the pages
+pub struct FixtureEventSource {
+    pages: Vec<EventsPage>,
+}
+
+impl FixtureEventSource {
+    pub fn new(pages: Vec<EventsPage>) -> Self {
+        FixtureEventSource { pages }
+    }
+
+    /// Reads the pages from a JSON file with a list of recorded results
+    pub fn read(path: &str) -> Self {
+        let contents = fs::read(path).expect("Failed to read event fixture");
+        let pages: Vec<EventsPage> =
+            serde_json::from_slice(&contents).expect("Failed to deserialize event fixture");
+        FixtureEventSource::new(pages)
+    }
+}
+
+impl EventSource for FixtureEventSource {
+    fn get_events(
+        &self,
+        contract: &FieldElement,
+        from_block: u64,
+        continuation_token: Option<&str>,
+    ) -> Result<EventsPage, String> {
+        let index = match continuation_token {
+            Some(token) => token
+                .parse::<usize>()
+                .map_err(|_| "Invalid continuation token".to_string())?,
+            None => 0,
+        };
+        let page = match self.pages.get(index) {
+            Some(page) => page,
+            None => return Ok(EventsPage::default()),
+        };
+
+        // Filter like a node would
+        let events = page
+            .events
+            .iter()
+            .filter(|e| FieldElement::from_str(&e.from_address).ok() == Some(*contract))
+            .filter(|e| !matches!(e.block_number, Some(b) if b < from_block))
+            .cloned()
+            .collect();
+        let continuation_token = match index + 1 < self.pages.len() {
+            true => Some((index + 1).to_string()),
+            false => None,
+        };
+        Ok(EventsPage {
+            events,
+            continuation_token,
+        })
+    }
+}
+
+/// Parses a Claimed event. Events of other kinds and of pending blocks are skipped
+pub fn parse_claimed(event: &EmittedEvent) -> Result<Option<ClaimedEvent>, String> {
+    let selector = FieldElement::from_str(CLAIMED_SELECTOR).unwrap();
+    let key = match event.keys.first() {
+        Some(key) => parse_felt(key)?,
+        None => return Ok(None),
+    };
+    let block_number = match event.block_number {
+        Some(block_number) if key == selector => block_number,
+        _ => return Ok(None),
+    };
+
+    // The members of the event that aren't keys are in the data: claimee, amount
+    if event.data.len() != 2 {
+        return Err("Invalid Claimed event data".to_string());
+    }
+    let amount = felt_to_u128(&parse_felt(&event.data[1])?)
+        .ok_or("Claimed amount is out of range".to_string())?;
+    Ok(Some(ClaimedEvent {
+        claimee: parse_felt(&event.data[0])?,
+        amount,
+        block_number,
+        transaction_hash: parse_felt(&event.transaction_hash)?,
+    }))
+}
+
+fn parse_felt(value: &str) -> Result<FieldElement, String> {
+    FieldElement::from_str(value).map_err(|e| e.to_string())
+}
+
+impl ClaimIndex {
+    /// Adds an event. The claimed amount of the address is the largest amount claimed,
+    /// since each claim sets the cumulative amount
+    pub fn add(&mut self, event: ClaimedEvent) {
+        let claimed = self.claimed.entry(event.claimee).or_insert(0);
+        *claimed = (*claimed).max(event.amount);
+        self.last_block = self.last_block.max(Some(event.block_number));
+        self.events.push(event);
+    }
+
+    /// The amount the address has claimed in total
+    pub fn claimed(&self, address: &FieldElement) -> u128 {
+        self.claimed.get(address).copied().unwrap_or(0)
+    }
+}
+
+/// Reads the Claimed events after the indexed blocks and adds them to the index. Returns the number of new events
+pub fn sync_claims(
+    source: &dyn EventSource,
+    contract: &FieldElement,
+    index: &mut ClaimIndex,
+) -> Result<usize, String> {
+    // The last indexed block is read again, as it may have gotten more events after it was read
+    let from_block = index.last_block.unwrap_or(0);
+    let mut events: Vec<ClaimedEvent> = vec![];
+    let mut continuation_token: Option<String> = None;
+    loop {
+        let page = source.get_events(contract, from_block, continuation_token.as_deref())?;
+        // Events of other contracts are skipped in case the source doesn't filter them
+        for event in page
+            .events
+            .iter()
+            .filter(|e| parse_felt(&e.from_address).ok() == Some(*contract))
+        {
+            if let Some(claimed) = parse_claimed(event)? {
+                events.push(claimed);
+            }
+        }
+        continuation_token = page.continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+
+    let mut known: HashSet<ClaimedEvent> = index.events.iter().cloned().collect();
+    let mut added = 0;
+    for event in events {
+        if known.insert(event.clone()) {
+            info!(
+                claimee = %format_address(&event.claimee),
+                amount = %event.amount,
+                block_number = event.block_number,
+                "Indexed Claimed event"
+            );
+            index.add(event);
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// The amount of a round's allocations that is claimed.
+/// The Distributor pays out the allocations without a token, so the claims only count towards those
+pub fn claimed_in_round(round: &RoundTreeData, index: &ClaimIndex) -> u128 {
+    round
+        .tree
+        .allocations
+        .iter()
+        .filter(|a| a.token.is_none())
+        .map(|a| index.claimed(&a.address).min(a.cumulative_amount))
+        .sum()
+}
+
+/// The amount of a round's allocations without a token that isn't claimed yet
+pub fn unclaimed_in_round(round: &RoundTreeData, index: &ClaimIndex) -> u128 {
+    round.accumulated_total_amount - claimed_in_round(round, index)
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct StoredClaims {
+    last_block: Option<u64>,
+    events: Vec<StoredClaimedEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StoredClaimedEvent {
+    claimee: String,
+    amount: String,
+    block_number: u64,
+    transaction_hash: String,
+}
+
+/// Reads the indexed events from the given file. Empty if there's no such file
+pub fn read_claims(path: &str) -> ClaimIndex {
+    let mut index = ClaimIndex::default();
+    if !Path::new(path).exists() {
+        return index;
+    }
+    let contents = fs::read(path).expect("Failed to read claims");
+    let stored: StoredClaims =
+        serde_json::from_slice(&contents).expect("Failed to deserialize claims");
+    for event in stored.events {
+        index.add(ClaimedEvent {
+            claimee: FieldElement::from_str(&event.claimee).expect("Invalid claimee"),
+            amount: event.amount.parse().expect("Invalid claimed amount"),
+            block_number: event.block_number,
+            transaction_hash: FieldElement::from_str(&event.transaction_hash)
+                .expect("Invalid transaction hash"),
+        });
+    }
+    index.last_block = index.last_block.max(stored.last_block);
+    info!(events = index.events.len(), last_block = ?index.last_block, "Read claims");
+    index
+}
+
+/// Writes the indexed events to the given file. The file is replaced only once it's fully written
+pub fn write_claims(path: &str, index: &ClaimIndex) -> Result<(), String> {
+    let stored = StoredClaims {
+        last_block: index.last_block,
+        events: index
+            .events
+            .iter()
+            .map(|e| StoredClaimedEvent {
+                claimee: format_address(&e.claimee),
+                amount: e.amount.to_string(),
+                block_number: e.block_number,
+                transaction_hash: felt_to_b16(&e.transaction_hash),
+            })
+            .collect(),
+    };
+    let contents = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+    let temp_path = format!("{}.tmp", path);
+    fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp_path, path).map_err(|e| e.to_string())
+}
+
+/// Settings of the indexer, read from the environment
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    /// The Starknet JSON-RPC endpoint, from STARKNET_RPC_URL
+    pub rpc_url: String,
+    /// The Distributor contract, from DISTRIBUTOR_ADDRESS
+    pub contract: FieldElement,
+    /// Where the indexed events are stored, from CLAIMS_FILE. Defaults to ./claims.json
+    pub claims_file: String,
+    /// How often new events are read, from CLAIMS_POLL_SECONDS. Defaults to 60 seconds
+    pub poll_interval: Duration,
+}
+
+impl IndexerConfig {
+    /// None if STARKNET_RPC_URL or DISTRIBUTOR_ADDRESS isn't set, in which case no events are indexed
+    pub fn from_env() -> Option<Self> {
+        let rpc_url = env::var("STARKNET_RPC_URL").ok()?;
+        let contract = env::var("DISTRIBUTOR_ADDRESS").ok()?;
+        let poll_seconds = env::var("CLAIMS_POLL_SECONDS")
+            .ok()
+            .map(|v| v.parse::<u64>().expect("Invalid CLAIMS_POLL_SECONDS"))
+            .unwrap_or(60);
+        Some(IndexerConfig {
+            rpc_url,
+            contract: FieldElement::from_str(&contract).expect("Invalid DISTRIBUTOR_ADDRESS"),
+            claims_file: claims_file(),
+            poll_interval: Duration::from_secs(poll_seconds),
+        })
+    }
+}
+
+/// The file the indexed events are stored in
+pub fn claims_file() -> String {
+    env::var("CLAIMS_FILE").unwrap_or_else(|_| "./claims.json".to_string())
+}
+
+/// Keeps indexing new Claimed events in a background thread. Each sync is stored in the claims file
+pub fn start_indexer(config: IndexerConfig) -> thread::JoinHandle<()> {
+    info!(rpc_url = %config.rpc_url, contract = %format_address(&config.contract), "Starting claims indexer");
+    thread::spawn(move || {
+        let source = RpcEventSource::new(&config.rpc_url);
+        loop {
+            let mut index = get_api_data().claims.clone();
+            match sync_claims(&source, &config.contract, &mut index) {
+                Ok(added) => {
+                    if added > 0 {
+                        if let Err(e) = write_claims(&config.claims_file, &index) {
+                            warn!(error = %e, "Failed to store claims");
+                        }
+                    }
+                    info!(added, last_block = ?index.last_block, "Claims synced");
+                    set_claims(index);
+                }
+                Err(e) => warn!(error = %e, "Failed to sync claims"),
+            }
+            thread::sleep(config.poll_interval);
+        }
+    })
+}
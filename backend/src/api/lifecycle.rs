@@ -0,0 +1,106 @@
+use serde_json::{from_slice, to_vec_pretty};
+use std::{borrow::Borrow, collections::BTreeMap, fmt, fs, path::Path};
+use tracing::info;
+
+use super::structs::{CampaignConfig, RoundState, RoundTreeData, Visibility};
+
+impl fmt::Display for RoundState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RoundState::Draft => "draft",
+            RoundState::Validated => "validated",
+            RoundState::Approved => "approved",
+            RoundState::Published => "published",
+            RoundState::Retired => "retired",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The state of a round. Rounds that aren't in round_states.json are drafts.
+/// A round whose root is in its slot of the contract is published, unless it's retired
+pub fn round_state(round: &RoundTreeData, config: &CampaignConfig) -> RoundState {
+    let state = config
+        .round_states
+        .get(&round.round)
+        .copied()
+        .unwrap_or_default();
+    let published = round
+        .slot
+        .and_then(|slot| config.published_roots.get(slot as usize))
+        == Some(&round.tree.root);
+    match state {
+        RoundState::Retired => RoundState::Retired,
+        _ if published => RoundState::Published,
+        _ => state,
+    }
+}
+
+/// Whether rounds in a state are served. Drafts and validated rounds only in previews, retired rounds never
+pub fn is_served(state: RoundState, visibility: Visibility) -> bool {
+    match state {
+        RoundState::Approved | RoundState::Published => true,
+        RoundState::Draft | RoundState::Validated => visibility == Visibility::Preview,
+        RoundState::Retired => false,
+    }
+}
+
+/// The rounds that are served, so the latest of them is the latest approved round or, in previews, the latest draft
+pub fn served_rounds<R: Borrow<RoundTreeData> + Clone>(
+    rounds: &[R],
+    config: &CampaignConfig,
+    visibility: Visibility,
+) -> Vec<R> {
+    rounds
+        .iter()
+        .filter(|r| {
+            let data: &RoundTreeData = (*r).borrow();
+            is_served(round_state(data, config), visibility)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Checks a change of state made by an operator. Rounds only become validated by passing the validation,
+/// and published once their root is in published_roots.json
+pub fn check_transition(from: RoundState, to: RoundState) -> Result<(), String> {
+    match (from, to) {
+        (RoundState::Validated, RoundState::Approved) => Ok(()),
+        (from, RoundState::Retired) if from != RoundState::Retired => Ok(()),
+        _ => Err(format!("A round can't go from {} to {}", from, to)),
+    }
+}
+
+/// Reads the states of the rounds from round_states.json in the input folder. Empty if there's no such file.
+/// The rounds in disabled_rounds.json, written by earlier versions, are retired
+pub fn read_round_states(filepath: &str) -> BTreeMap<u8, RoundState> {
+    let path = Path::new(filepath).join("round_states.json");
+    let mut states: BTreeMap<u8, RoundState> = match path.exists() {
+        true => {
+            let contents = fs::read(&path).expect("Failed to read round states");
+            from_slice(&contents).expect("Failed to deserialize round states")
+        }
+        false => BTreeMap::new(),
+    };
+
+    let disabled_path = Path::new(filepath).join("disabled_rounds.json");
+    if disabled_path.exists() {
+        let contents = fs::read(&disabled_path).expect("Failed to read disabled rounds");
+        let disabled: Vec<u8> =
+            from_slice(&contents).expect("Failed to deserialize disabled rounds");
+        for round in disabled {
+            states.insert(round, RoundState::Retired);
+        }
+    }
+    info!(rounds = states.len(), "Read round states");
+    states
+}
+
+pub fn write_round_states(filepath: &str, states: &BTreeMap<u8, RoundState>) -> Result<(), String> {
+    let contents = to_vec_pretty(states).map_err(|e| e.to_string())?;
+    // Written to a temporary file first, so a crash can't leave a truncated file that stops the next startup
+    let path = Path::new(filepath).join("round_states.json");
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp_path, &path).map_err(|e| e.to_string())
+}
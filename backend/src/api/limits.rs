@@ -0,0 +1,172 @@
+use serde::Deserialize;
+use starknet_crypto::FieldElement;
+use std::collections::HashMap;
+use tracing::info;
+
+use super::{
+    address::format_address,
+    math::pro_rata,
+    structs::{AllocationKey, CampaignConfig, LimitKind, LimitRecord},
+};
+
+/// What happens to the amounts cut by the caps
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CapPolicy {
+    /// The amounts aren't given to anyone
+    #[default]
+    Burn,
+    /// The amounts are shared among the other addresses of the round that are below the caps,
+    /// pro rata to their amounts in the round
+    Redistribute,
+}
+
+/// What the limits need to remember from the previous rounds
+#[derive(Debug, Default)]
+pub struct LimitState {
+    /// The amount of each allocation released in all rounds so far, after the caps
+    earned: HashMap<AllocationKey, u128>,
+    /// The amount of each allocation waiting to reach the minimum claim
+    carried: HashMap<AllocationKey, u128>,
+}
+
+/// Applies the caps and the minimum claim to the amounts of one round.
+/// Carried amounts are added first, also for addresses without allocations in the round, so the caps cover them too.
+/// Returns the records of the changed allocations and the amount burned
+pub fn apply_limits(
+    round: u8,
+    round_amounts: &mut HashMap<AllocationKey, u128>,
+    state: &mut LimitState,
+    config: &CampaignConfig,
+) -> (Vec<LimitRecord>, u128) {
+    let mut records: Vec<LimitRecord> = vec![];
+    let mut excess: HashMap<Option<FieldElement>, u128> = HashMap::new();
+    // How much more each allocation can get this round before hitting a cap
+    let mut room: HashMap<AllocationKey, u128> = HashMap::new();
+
+    for (key, carried) in state.carried.drain() {
+        *round_amounts.entry(key).or_insert(0) += carried;
+    }
+
+    let mut keys: Vec<AllocationKey> = round_amounts.keys().copied().collect();
+    keys.sort();
+
+    for key in keys.iter() {
+        let amount = round_amounts.get_mut(key).unwrap();
+        let mut cut = |kind: LimitKind, cut_amount: u128| {
+            records.push(record(round, key, kind, cut_amount));
+            *excess.entry(key.1).or_insert(0) += cut_amount;
+        };
+
+        if let Some(cap) = config.round_cap {
+            if *amount > cap {
+                cut(LimitKind::RoundCap, *amount - cap);
+                *amount = cap;
+            }
+        }
+        let earned = state.earned.get(key).copied().unwrap_or(0);
+        let cumulative_room = config.cumulative_cap.map(|cap| cap.saturating_sub(earned));
+        if let Some(cumulative_room) = cumulative_room {
+            if *amount > cumulative_room {
+                cut(LimitKind::CumulativeCap, *amount - cumulative_room);
+                *amount = cumulative_room;
+            }
+        }
+
+        let round_room = config.round_cap.map(|cap| cap - *amount);
+        let cumulative_room = cumulative_room.map(|room| room - *amount);
+        room.insert(
+            *key,
+            round_room
+                .unwrap_or(u128::MAX)
+                .min(cumulative_room.unwrap_or(u128::MAX)),
+        );
+    }
+
+    let mut burned_amount = 0_u128;
+    let mut tokens: Vec<Option<FieldElement>> = excess.keys().copied().collect();
+    tokens.sort();
+    for token in tokens {
+        burned_amount += match config.cap_policy {
+            CapPolicy::Burn => excess[&token],
+            CapPolicy::Redistribute => {
+                share_excess(round_amounts, &mut room, token, excess[&token])
+            }
+        };
+    }
+
+    // Once the released amount of an allocation reaches the minimum claim, its later amounts are released too
+    if let Some(min_claim) = config.min_claim {
+        for key in keys.iter() {
+            let pending = round_amounts[key];
+            let earned = state.earned.get(key).copied().unwrap_or(0);
+            if earned + pending < min_claim {
+                round_amounts.remove(key);
+                if pending > 0 {
+                    state.carried.insert(*key, pending);
+                    records.push(record(round, key, LimitKind::BelowMinClaim, pending));
+                }
+            }
+        }
+    }
+
+    for (key, amount) in round_amounts.iter() {
+        *state.earned.entry(*key).or_insert(0) += amount;
+    }
+    records.sort_by_key(|r| (r.address, r.token));
+
+    for record in records.iter() {
+        info!(
+            round,
+            address = %format_address(&record.address),
+            token = ?record.token.as_ref().map(format_address),
+            kind = ?record.kind,
+            amount = %record.amount,
+            "Limited allocation"
+        );
+    }
+    (records, burned_amount)
+}
+
+/// Shares an amount among the allocations of a token that are below the caps, pro rata to their amounts.
+/// Shares that don't fit below the caps are shared again among the rest. Returns what couldn't be shared
+fn share_excess(
+    round_amounts: &mut HashMap<AllocationKey, u128>,
+    room: &mut HashMap<AllocationKey, u128>,
+    token: Option<FieldElement>,
+    mut excess: u128,
+) -> u128 {
+    while excess > 0 {
+        let recipients: Vec<(AllocationKey, u128)> = round_amounts
+            .iter()
+            .filter(|(key, amount)| key.1 == token && **amount > 0 && room[*key] > 0)
+            .map(|(key, amount)| (*key, *amount))
+            .collect();
+        let shares = pro_rata(excess, &recipients);
+        if shares.is_empty() {
+            break;
+        }
+
+        // Every round either shares everything or fills the room of at least one allocation
+        let mut left = 0_u128;
+        for (key, share) in shares {
+            let key_room = room.get_mut(&key).unwrap();
+            let given = share.min(*key_room);
+            *key_room -= given;
+            *round_amounts.get_mut(&key).unwrap() += given;
+            left += share - given;
+        }
+        excess = left;
+    }
+    excess
+}
+
+fn record(round: u8, key: &AllocationKey, kind: LimitKind, amount: u128) -> LimitRecord {
+    LimitRecord {
+        round,
+        address: key.0,
+        token: key.1,
+        kind,
+        amount,
+    }
+}
@@ -0,0 +1,26 @@
+use starknet_crypto::{poseidon_hash_single, FieldElement};
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+use super::merkle_tree::felt_to_b16;
+
+/// Initializes JSON logging to stdout.
+/// The log level is read from the RUST_LOG environment variable, e.g. RUST_LOG=debug. Defaults to info
+pub fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(true)
+        .init();
+}
+
+/// Hashes an address so that it can be logged without exposing the address itself
+pub fn address_hash(address: &str) -> String {
+    match FieldElement::from_str(address) {
+        Ok(felt) => felt_to_b16(&poseidon_hash_single(felt)),
+        Err(_) => "invalid".to_string(),
+    }
+}
@@ -0,0 +1,73 @@
+use starknet_crypto::FieldElement;
+use std::cmp::Reverse;
+
+/// value * numerator / denominator rounded down.
+/// The product is computed with 256 bits so it can't overflow, the result has to fit in u128
+pub fn mul_div(value: u128, numerator: u128, denominator: u128) -> u128 {
+    let (high, low) = widening_mul(value, numerator);
+
+    // Long division one bit at a time
+    let mut quotient = 0_u128;
+    let mut remainder = 0_u128;
+    for i in (0..256).rev() {
+        let bit = match i >= 128 {
+            true => (high >> (i - 128)) & 1,
+            false => (low >> i) & 1,
+        };
+        let overflow = remainder >> 127 == 1;
+        remainder = (remainder << 1) | bit;
+        quotient <<= 1;
+        if overflow || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    quotient
+}
+
+/// Splits a total among recipients pro rata to their weights, rounded down. Empty if the weights add up to 0.
+/// The rounding dust goes to the largest weight, ties by key, so it always goes to the same recipient
+pub fn pro_rata<K: Ord + Copy>(total: u128, recipients: &[(K, u128)]) -> Vec<(K, u128)> {
+    let weights_total: u128 = recipients.iter().map(|(_, weight)| weight).sum();
+    if weights_total == 0 {
+        return vec![];
+    }
+    let mut shares: Vec<(K, u128)> = recipients
+        .iter()
+        .map(|(key, weight)| (*key, mul_div(total, *weight, weights_total)))
+        .collect();
+    let largest = recipients
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, (key, weight))| (Reverse(*weight), *key))
+        .map(|(i, _)| i)
+        .unwrap();
+    shares[largest].1 += total - shares.iter().map(|(_, share)| share).sum::<u128>();
+    shares
+}
+
+/// The full 256 bit product of two values as (high, low) halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    let mask = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (high_low & mask) + (low_high & mask);
+    let low = (middle << 64) | (low_low & mask);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// The value of a felt as u128. None if it doesn't fit
+pub fn felt_to_u128(felt: &FieldElement) -> Option<u128> {
+    let bytes = felt.to_bytes_be();
+    if bytes[..16].iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(u128::from_be_bytes(bytes[16..].try_into().unwrap()))
+}
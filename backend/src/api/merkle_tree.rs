@@ -1,159 +1,365 @@
-use starknet_crypto::{pedersen_hash, poseidon_hash, FieldElement};
-use std::{collections::HashSet, str::FromStr, vec};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use starknet_crypto::FieldElement;
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::size_of,
+    ops::Range,
+    str::FromStr,
+    time::Instant,
+    vec,
+};
+use tracing::{info, instrument};
 
-use super::structs::{CairoCalldata, CumulativeAllocation, MerkleTree, Node};
+use super::{
+    address::{format_address, parse_query},
+    errors::{ApiError, ErrorKind},
+    hashing::{HashScheme, StandardHasher, TreeHasher},
+    metrics::record_tree_build,
+    structs::{CairoCalldata, CairoMultiproofCalldata, CumulativeAllocation, MerkleTree},
+};
 
 impl MerkleTree {
+    /// Builds a tree with the standard hash scheme of the Distributor contract.
+    /// The allocations are reordered by address and token, see `with_hasher`
     pub fn new(allocations: Vec<CumulativeAllocation>) -> Self {
-        if allocations.len() == 0 {
+        MerkleTree::with_hasher(allocations, HashScheme::Standard)
+    }
+}
+
+impl<H: TreeHasher> MerkleTree<H> {
+    /// Builds a tree with the given hash scheme.
+    /// The allocations are sorted by (address, token) before hashing, so the root doesn't
+    /// depend on the order they are passed in. The tree's `allocations` keep that sorted order
+    #[instrument(name = "build_tree", skip_all, fields(leaves = allocations.len(), hasher = ?hasher))]
+    pub fn with_hasher(mut allocations: Vec<CumulativeAllocation>, hasher: H) -> Self {
+        if allocations.is_empty() {
             panic!("No data for merkle tree");
         }
-        let mut leaves: Vec<Node> = allocations
-            .clone()
-            .into_iter()
-            .map(|a| Node::new_leaf(a))
-            .collect();
-
-        // if odd length add a copy of last elem
-        if leaves.len() % 2 == 1 {
-            leaves.push(leaves.last().unwrap().clone());
-        }
+        let start = Instant::now();
 
-        let root = build_tree(leaves);
+        // Leaves are kept in address order so a leaf's position can be found from its address.
+        // The leaves of one address are ordered by token, a leaf without a token first
+        allocations.sort_by_key(|a| (a.address, a.token));
 
-        MerkleTree { root, allocations }
-    }
+        let levels = build_levels(&hasher, hash_leaves(&hasher, &allocations));
+        let root = levels.last().unwrap()[0];
+        let address_index = index_addresses(&allocations);
 
-    pub fn address_calldata(&self, address: &str) -> Result<CairoCalldata, String> {
-        let felt_address = FieldElement::from_str(address).map_err(|e| e.to_string())?;
+        record_tree_build(start.elapsed().as_secs_f64());
+        info!(
+            duration_ms = start.elapsed().as_millis() as u64,
+            "Built Merkle tree"
+        );
 
-        if !&self.root.accessible_addresses.contains(&felt_address) {
-            return Err("Address not found in tree".to_string());
+        MerkleTree {
+            root,
+            levels,
+            allocations,
+            address_index,
+            hasher,
         }
-        let mut hashes: Vec<FieldElement> = vec![];
-        let mut current_node = &self.root;
-        // if either child is_some, then both is_some
-        loop {
-            let left = current_node.left_child.as_ref().unwrap();
-            let right = current_node.right_child.as_ref().unwrap();
-            if left.accessible_addresses.contains(&felt_address) {
-                hashes.push(right.value);
-                current_node = left;
-            } else {
-                hashes.push(left.value);
-                current_node = right;
-            }
-            if current_node.left_child.is_none() {
-                break;
-            }
-        }
-        // reverse to leaf first root last
-        hashes = hashes.into_iter().rev().collect();
+    }
 
-        let allocation = self
+    /// Rough estimate of the heap and stack memory used by the tree, in bytes
+    pub fn estimated_size(&self) -> usize {
+        let hashes: usize = self.levels.iter().map(|l| l.capacity()).sum();
+        let extra_fields: usize = self
             .allocations
             .iter()
-            .find(|a| &a.address == &felt_address)
-            .unwrap();
+            .map(|a| a.extra_fields.capacity())
+            .sum();
+        size_of::<MerkleTree<H>>()
+            + (hashes + extra_fields) * size_of::<FieldElement>()
+            + self.levels.capacity() * size_of::<Vec<FieldElement>>()
+            + self.allocations.capacity() * size_of::<CumulativeAllocation>()
+            + self.address_index.capacity() * size_of::<(FieldElement, Range<usize>)>()
+    }
 
+    pub fn address_calldata(&self, address: &str) -> Result<CairoCalldata, ApiError> {
+        self.address_token_calldata(address, None)
+    }
+
+    /// Calldata for the leaf of an address and token. Without a token it's the leaf without a token
+    pub fn address_token_calldata(
+        &self,
+        address: &str,
+        token: Option<&str>,
+    ) -> Result<CairoCalldata, ApiError> {
+        let (felt_address, felt_token) = parse_query(address, token)?;
+
+        let index = match self.find_leaf(&felt_address, felt_token.as_ref()) {
+            Some(v) => v,
+            None => return Err(address_not_found()),
+        };
+
+        let allocation = &self.allocations[index];
         let amount = FieldElement::from(allocation.cumulative_amount);
 
-        let hash_strings = hashes.iter().map(felt_to_b16).collect();
+        let hash_strings = self.proof(index).iter().map(felt_to_b16).collect();
 
         let calldata = CairoCalldata {
             amount: felt_to_b16(&amount),
             proof: hash_strings,
+            token: allocation.token.as_ref().map(format_address),
+            extra_fields: allocation.extra_fields.iter().map(felt_to_b16).collect(),
         };
         Ok(calldata)
+    }
+
+    /// Position of the leaf of an address and token, if there is one.
+    /// Without a token only the leaf without a token matches, which is the one the Distributor pays out
+    pub fn find_leaf(&self, address: &FieldElement, token: Option<&FieldElement>) -> Option<usize> {
+        let mut range = self.address_index.get(address)?.clone();
+        range.find(|i| self.allocations[*i].token.as_ref() == token)
+    }
+
+    /// Generates one proof for several addresses at once, covering all leaves of the addresses.
+    /// The leaves are returned in the order the verifier consumes them
+    pub fn multiproof_calldata(
+        &self,
+        addresses: &[String],
+    ) -> Result<CairoMultiproofCalldata, ApiError> {
+        if addresses.is_empty() {
+            return Err(ApiError::new(ErrorKind::Other, "No addresses given"));
+        }
+        let mut indices: Vec<usize> = vec![];
+        for address in addresses.iter() {
+            let (felt_address, _) = parse_query(address, None)?;
+            match self.address_index.get(&felt_address) {
+                Some(range) => indices.extend(range.clone()),
+                None => return Err(address_not_found()),
+            }
+        }
+        indices.sort();
+        indices.dedup();
+
+        let (proof, proof_flags) = self.multiproof(indices.clone());
 
-        /*     let mut calldata = vec![address, amount];
-        calldata.append(&mut hashStrings);
+        let leaves: Vec<&CumulativeAllocation> =
+            indices.iter().map(|i| &self.allocations[*i]).collect();
+
+        Ok(CairoMultiproofCalldata {
+            addresses: leaves.iter().map(|a| format_address(&a.address)).collect(),
+            amounts: leaves
+                .iter()
+                .map(|a| felt_to_b16(&FieldElement::from(a.cumulative_amount)))
+                .collect(),
+            tokens: leaves
+                .iter()
+                .map(|a| a.token.as_ref().map(format_address))
+                .collect(),
+            extra_fields: leaves
+                .iter()
+                .map(|a| a.extra_fields.iter().map(felt_to_b16).collect())
+                .collect(),
+            proof: proof.iter().map(felt_to_b16).collect(),
+            proof_flags,
+        })
+    }
 
-        // in order to be readable by FE needs to be base16 string
-        Ok(calldata.iter().map(felt_to_b16).collect()) */
+    /// Hashes and flags needed to compute the root from the leaves at the given indices.
+    /// The indices have to be sorted so that siblings are next to each other
+    fn multiproof(&self, mut indices: Vec<usize>) -> (Vec<FieldElement>, Vec<bool>) {
+        let mut proof: Vec<FieldElement> = vec![];
+        let mut proof_flags: Vec<bool> = vec![];
+
+        // The root level has no siblings
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            let mut next_indices: Vec<usize> = vec![];
+            let mut i = 0;
+            while i < indices.len() {
+                let index = indices[i];
+                let sibling = index ^ 1;
+                if indices.get(i + 1) == Some(&sibling) {
+                    // Both nodes of the pair are known
+                    proof_flags.push(true);
+                    i += 2;
+                } else {
+                    proof_flags.push(false);
+                    proof.push(level[sibling]);
+                    i += 1;
+                }
+                // Parents keep the (reversed) order of the nodes, so siblings stay next to each other
+                next_indices.push(parent_index(level.len(), index));
+            }
+            indices = next_indices;
+        }
+        (proof, proof_flags)
+    }
+
+    /// Sibling hashes from the leaf at the given index up to the root. Leaf first root last
+    fn proof(&self, mut index: usize) -> Vec<FieldElement> {
+        let mut hashes: Vec<FieldElement> = vec![];
+        // The root level has no siblings
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            hashes.push(level[index ^ 1]);
+            index = parent_index(level.len(), index);
+        }
+        hashes
     }
 }
 
-impl Node {
-    fn new(a: Node, b: Node) -> Self {
-        let (left_child, right_child) = match a.value.lt(&b.value) {
-            true => (a, b),
-            false => (b, a),
+/// Verifies a proof generated by `MerkleTree::multiproof_calldata` against a root.
+/// Works like the single proof verification of the contract, with the same hash scheme as the tree
+pub fn verify_multiproof<H: TreeHasher>(
+    hasher: &H,
+    root: &FieldElement,
+    calldata: &CairoMultiproofCalldata,
+) -> bool {
+    let parse = |values: &[String]| -> Option<Vec<FieldElement>> {
+        values
+            .iter()
+            .map(|v| FieldElement::from_str(v).ok())
+            .collect()
+    };
+    let (addresses, amounts, proof) = match (
+        parse(&calldata.addresses),
+        parse(&calldata.amounts),
+        parse(&calldata.proof),
+    ) {
+        (Some(addresses), Some(amounts), Some(proof)) => (addresses, amounts, proof),
+        _ => return false,
+    };
+    if addresses.is_empty()
+        || addresses.len() != amounts.len()
+        || addresses.len() != calldata.tokens.len()
+        || addresses.len() != calldata.extra_fields.len()
+    {
+        return false;
+    }
+    let mut leaves: Vec<FieldElement> = vec![];
+    for (((address, amount), token), extra_fields) in addresses
+        .iter()
+        .zip(amounts.iter())
+        .zip(calldata.tokens.iter())
+        .zip(calldata.extra_fields.iter())
+    {
+        let token = match token {
+            Some(token) => match FieldElement::from_str(token) {
+                Ok(token) => Some(token),
+                Err(_) => return false,
+            },
+            None => None,
+        };
+        let (amount, extra_fields) = match (u128::try_from(*amount), parse(extra_fields)) {
+            (Ok(amount), Some(extra_fields)) => (amount, extra_fields),
+            _ => return false,
+        };
+        leaves.push(hasher.hash_leaf(&CumulativeAllocation {
+            address: *address,
+            cumulative_amount: amount,
+            token,
+            extra_fields,
+        }));
+    }
+
+    // Leaves are consumed first, then the hashes in the order they were computed
+    let mut queue: VecDeque<FieldElement> = leaves.into_iter().collect();
+    let mut proof = proof.into_iter();
+
+    for flag in calldata.proof_flags.iter() {
+        let a = queue.pop_front();
+        let b = match flag {
+            true => queue.pop_front(),
+            false => proof.next(),
         };
-        let value = hash(&left_child.value, &right_child.value);
-        let mut accessible_addresses = HashSet::new();
-        accessible_addresses.extend(left_child.accessible_addresses.clone());
-        accessible_addresses.extend(right_child.accessible_addresses.clone());
-
-        Node {
-            left_child: Some(Box::new(left_child)),
-            right_child: Some(Box::new(right_child)),
-            accessible_addresses,
-            value,
+        match (a, b) {
+            (Some(a), Some(b)) => queue.push_back(hasher.hash_node(&a, &b)),
+            _ => return false,
         }
     }
-    fn new_leaf(allocation: CumulativeAllocation) -> Self {
-        let address = allocation.address;
-        let cumulated_amount = FieldElement::from(allocation.cumulative_amount);
-        // keep order address, amount
-        let value = poseidon_hash(address, cumulated_amount);
-
-        Node {
-            left_child: None,
-            right_child: None,
-            accessible_addresses: vec![address].into_iter().collect(),
-            value,
+
+    // All of the proof has to be used and only the root may remain
+    proof.next().is_none() && queue.len() == 1 && queue[0] == *root
+}
+
+/// Builds the tree one level at a time, from the leaves up to the root.
+/// Only the hashes are kept: every level except the root has an even length,
+/// an odd level is padded by pairing its last hash with itself
+fn build_levels<H: TreeHasher>(hasher: &H, mut level: Vec<FieldElement>) -> Vec<Vec<FieldElement>> {
+    let mut levels: Vec<Vec<FieldElement>> = vec![];
+    loop {
+        if level.len() % 2 == 1 {
+            level.push(*level.last().unwrap());
+        }
+        let next_level = hash_level(hasher, &level);
+        levels.push(level);
+
+        if next_level.len() == 1 {
+            levels.push(next_level);
+            return levels;
         }
+        level = next_level;
     }
 }
 
-enum TreeBuilder {
-    KeepGoing(Vec<Node>),
-    Done(Node),
+/// Position of a node's parent in the next level.
+/// Pairs are hashed starting from the end of a level, so the order is reversed on every level.
+/// The order matters: changing it would change the root
+fn parent_index(level_len: usize, index: usize) -> usize {
+    level_len / 2 - 1 - index / 2
 }
 
-fn build_tree(leaves: Vec<Node>) -> Node {
-    match build_tree_recursively(TreeBuilder::KeepGoing(leaves)) {
-        TreeBuilder::Done(root) => return root,
-        _ => unreachable!("Failed building the tree"),
+/// Maps each address to the positions of its leaves in the sorted allocations
+fn index_addresses(allocations: &[CumulativeAllocation]) -> HashMap<FieldElement, Range<usize>> {
+    let mut index: HashMap<FieldElement, Range<usize>> = HashMap::with_capacity(allocations.len());
+    for (i, allocation) in allocations.iter().enumerate() {
+        index
+            .entry(allocation.address)
+            .and_modify(|range| range.end = i + 1)
+            .or_insert(i..i + 1);
     }
+    index
 }
 
-fn build_tree_recursively(tree_builder: TreeBuilder) -> TreeBuilder {
-    let mut nodes = match tree_builder {
-        TreeBuilder::KeepGoing(nodes) => nodes,
-        _ => unreachable!("Failed building the tree"),
-    };
-
-    let mut next_nodes: Vec<Node> = vec![];
+#[cfg(feature = "parallel")]
+fn hash_leaves<H: TreeHasher>(
+    hasher: &H,
+    allocations: &[CumulativeAllocation],
+) -> Vec<FieldElement> {
+    allocations
+        .par_iter()
+        .map(|a| hasher.hash_leaf(a))
+        .collect()
+}
 
-    while nodes.len() > 0 {
-        let a = nodes.pop().unwrap();
-        let b = nodes.pop().unwrap();
-        next_nodes.push(Node::new(a, b));
-    }
+#[cfg(not(feature = "parallel"))]
+fn hash_leaves<H: TreeHasher>(
+    hasher: &H,
+    allocations: &[CumulativeAllocation],
+) -> Vec<FieldElement> {
+    allocations.iter().map(|a| hasher.hash_leaf(a)).collect()
+}
 
-    if next_nodes.len() == 1 {
-        // return root
-        let root = next_nodes.pop().unwrap();
-        return TreeBuilder::Done(root);
-    }
+// Both versions keep the order of the pairs so the resulting level is identical
+#[cfg(feature = "parallel")]
+fn hash_level<H: TreeHasher>(hasher: &H, level: &[FieldElement]) -> Vec<FieldElement> {
+    level
+        .par_chunks(2)
+        .rev()
+        .map(|p| hasher.hash_node(&p[0], &p[1]))
+        .collect()
+}
 
-    if next_nodes.len() % 2 == 1 {
-        // if odd - pair last element with itself
-        next_nodes.push(next_nodes.last().unwrap().clone());
-    }
+#[cfg(not(feature = "parallel"))]
+fn hash_level<H: TreeHasher>(hasher: &H, level: &[FieldElement]) -> Vec<FieldElement> {
+    level
+        .chunks(2)
+        .rev()
+        .map(|p| hasher.hash_node(&p[0], &p[1]))
+        .collect()
+}
 
-    build_tree_recursively(TreeBuilder::KeepGoing(next_nodes))
+fn address_not_found() -> ApiError {
+    ApiError::new(ErrorKind::AddressNotFound, "Address not found in tree")
 }
 
 pub fn felt_to_b16(felt: &FieldElement) -> String {
     format!("{:#x}", felt)
 }
 
+/// Sorted-pair pedersen hash, used for the nodes of the standard hash scheme
 pub fn hash(a: &FieldElement, b: &FieldElement) -> FieldElement {
-    if a.lt(b) {
-        return pedersen_hash(a, b);
-    }
-    pedersen_hash(b, a)
+    StandardHasher.hash_node(a, b)
 }
@@ -0,0 +1,204 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    time::Instant,
+};
+
+use super::errors::{ApiError, ErrorKind};
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+    static ref HTTP_REQUESTS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("http_requests_total", "Number of HTTP requests handled"),
+        &["endpoint", "status"]
+    ));
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "Time spent handling HTTP requests"
+        ),
+        &["endpoint"]
+    ));
+    static ref API_ERRORS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("api_errors_total", "Number of errors returned by the API"),
+        &["kind"]
+    ));
+    static ref REJECTED_REQUESTS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new(
+            "rejected_requests_total",
+            "Number of requests rejected by the rate limits or the request size limits"
+        ),
+        &["endpoint", "reason"]
+    ));
+    static ref ROUND_LEAVES: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new(
+            "round_leaf_count",
+            "Number of leaves in the Merkle tree of a round"
+        ),
+        &["round"]
+    ));
+    static ref ROUND_BUILD_DURATION: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new(
+            "round_build_duration_milliseconds",
+            "Time it took to build the Merkle tree of a round"
+        ),
+        &["round"]
+    ));
+    static ref TREE_BUILD_DURATION: Histogram = register(Histogram::with_opts(
+        HistogramOpts::new(
+            "merkle_tree_build_duration_seconds",
+            "Time spent in building Merkle trees"
+        )
+        .buckets(vec![
+            0.01, 0.1, 1.0, 10.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0
+        ])
+    ));
+    static ref ALLOCATION_READ_DURATION: IntGauge = register(IntGauge::new(
+        "allocation_read_duration_milliseconds",
+        "Time it took to read and process all input files on the last reload"
+    ));
+    static ref DATA_RELOADS: IntCounter = register(IntCounter::new(
+        "data_reloads_total",
+        "Number of times the round data has been (re)loaded"
+    ));
+    static ref ROUND_DATA_MEMORY: IntGauge = register(IntGauge::new(
+        "round_data_memory_bytes",
+        "Estimated memory used by the round data held in memory"
+    ));
+}
+
+/// Registers a collector to the registry used by the /metrics endpoint
+fn register<T: prometheus::core::Collector + Clone + 'static>(
+    collector: prometheus::Result<T>,
+) -> T {
+    let collector = collector.expect("Invalid metric definition");
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("Failed to register metric");
+    collector
+}
+
+/// Renders all metrics in the Prometheus text format
+pub fn gather_metrics() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("Failed to encode metrics");
+    String::from_utf8(buffer).expect("Metrics are not valid UTF-8")
+}
+
+/// Counts an error returned by the API, labelled with its kind
+pub fn record_error(error: &ApiError) {
+    API_ERRORS
+        .with_label_values(&[error_kind(error.kind)])
+        .inc();
+}
+
+/// Counts a request rejected before reaching its endpoint
+pub fn record_rejection(endpoint: &str, reason: &str) {
+    REJECTED_REQUESTS
+        .with_label_values(&[endpoint, reason])
+        .inc();
+}
+
+/// The label of an error kind
+fn error_kind(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::RoundNotFound => "round_not_found",
+        ErrorKind::AddressNotFound => "address_not_found",
+        ErrorKind::RoundNotSequenced => "round_not_sequenced",
+        ErrorKind::RoundNotServed => "round_not_served",
+        ErrorKind::InvalidAddress => "invalid_address",
+        ErrorKind::Other => "other",
+    }
+}
+
+/// Stores leaf count and build duration of a round's tree
+pub fn record_round_build(round: u8, leaf_count: usize, duration_ms: u128) {
+    let round = round.to_string();
+    ROUND_LEAVES
+        .with_label_values(&[&round])
+        .set(leaf_count as i64);
+    ROUND_BUILD_DURATION
+        .with_label_values(&[&round])
+        .set(duration_ms as i64);
+}
+
+pub fn record_tree_build(duration_secs: f64) {
+    TREE_BUILD_DURATION.observe(duration_secs);
+}
+
+pub fn record_allocation_read(duration_ms: u128) {
+    ALLOCATION_READ_DURATION.set(duration_ms as i64);
+}
+
+/// Stores statistics of a finished (re)load of the round data
+pub fn record_reload(memory_bytes: usize) {
+    DATA_RELOADS.inc();
+    ROUND_DATA_MEMORY.set(memory_bytes as i64);
+}
+
+/// Middleware counting requests and measuring their latency per endpoint
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Use the route pattern so that unknown paths don't create new label values
+        let endpoint = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let start = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            HTTP_REQUEST_DURATION
+                .with_label_values(&[&endpoint])
+                .observe(start.elapsed().as_secs_f64());
+            HTTP_REQUESTS
+                .with_label_values(&[&endpoint, res.status().as_str()])
+                .inc();
+            Ok(res)
+        })
+    }
+}
@@ -1,5 +1,22 @@
+pub mod address;
+pub mod admin;
+pub mod caching;
 pub mod data_storage;
+pub mod distributor;
 pub mod endpoints;
+pub mod errors;
+pub mod exclusions;
+pub mod generator;
+pub mod hashing;
+pub mod indexer;
+pub mod lifecycle;
+pub mod limits;
+pub mod logging;
+pub mod math;
 pub mod merkle_tree;
+pub mod metrics;
 pub mod processor;
+pub mod rate_limit;
+pub mod sequencing;
 pub mod structs;
+pub mod vesting;
@@ -1,153 +1,321 @@
 use regex::Regex;
 use serde_json::from_slice;
 use starknet_crypto::FieldElement;
-use std::{collections::HashMap, fs::File, io::Read, path::Path, str::FromStr};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    fs::File,
+    io::{Read, Seek},
+    mem::size_of,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+use tracing::{debug, error, info, info_span, instrument, warn};
 
 use super::{
-    data_storage::get_all_data,
+    address::{format_address, parse_optional_address, parse_query, Address},
+    data_storage::{get_api_data, ApiData},
+    errors::{ApiError, ErrorKind},
+    exclusions::{apply_exclusions, read_denylist, Denylist},
+    indexer::{claimed_in_round, unclaimed_in_round},
+    lifecycle::{is_served, read_round_states, round_state, served_rounds},
+    limits::{apply_limits, LimitState},
     merkle_tree::felt_to_b16,
+    metrics::{record_allocation_read, record_round_build},
+    sequencing::{
+        assign_slots, claimable_round, publication_plan, read_published_roots, select_round,
+    },
     structs::{
-        CairoCalldata, CumulativeAllocation, FileNameInfo, JSONAllocation, MerkleTree,
-        RootQueryResult, RoundAmountMaps, RoundAmounts, RoundTreeData,
+        AllocationClaimsResult, AllocationKey, CairoCalldata, CairoMultiproofCalldata,
+        CampaignConfig, CumulativeAllocation, ExclusionResult, FileNameInfo, JSONAllocation,
+        LimitResult, MerkleTree, PublicationPlan, RootQueryResult, RoundAmountMaps, RoundAmounts,
+        RoundState, RoundSummaryResult, RoundTreeData, TokenTotals, TokenTotalsResult,
+        VestingRound, VestingSchedule, Visibility,
     },
+    vesting::{merge_vesting_rounds, read_vesting_schedules, vesting_preview},
 };
 use zip::ZipArchive;
 
-pub fn get_raw_calldata(round: Option<u8>, address: &String) -> Result<CairoCalldata, String> {
-    let relevant_data = match get_round_data(round) {
-        Ok(value) => value,
-        Err(value) => {
-            return Err(value);
-        }
-    };
+pub fn get_raw_calldata(
+    round: Option<u8>,
+    visibility: Visibility,
+    address: &str,
+    token: Option<&str>,
+) -> Result<CairoCalldata, ApiError> {
+    let relevant_data = get_round_data(&get_api_data(), round, visibility)?;
 
-    let calldata: CairoCalldata = match relevant_data.tree.address_calldata(&address) {
-        Ok(v) => v,
-        Err(value) => {
-            return Err(value);
-        }
-    };
-    Ok(calldata)
+    relevant_data.tree.address_token_calldata(address, token)
 }
 
-pub fn get_raw_allocation_amount(round: Option<u8>, address: &String) -> Result<u128, String> {
-    let relevant_data = match get_round_data(round) {
-        Ok(value) => value,
-        Err(value) => return Err(value),
-    };
+pub fn get_raw_multiproof_calldata(
+    round: Option<u8>,
+    visibility: Visibility,
+    addresses: &[String],
+) -> Result<CairoMultiproofCalldata, ApiError> {
+    let relevant_data = get_round_data(&get_api_data(), round, visibility)?;
 
-    let field: FieldElement = FieldElement::from_str(address).expect("Invalid address");
+    relevant_data.tree.multiproof_calldata(addresses)
+}
 
-    let drop = match relevant_data
-        .tree
-        .allocations
-        .iter()
-        .find(|a| a.address == field)
-    {
-        Some(v) => v,
-        None => return Ok(0_u128),
-    };
+pub fn get_raw_allocation_amount(
+    round: Option<u8>,
+    visibility: Visibility,
+    address: &str,
+    token: Option<&str>,
+) -> Result<u128, ApiError> {
+    let relevant_data = get_round_data(&get_api_data(), round, visibility)?;
+
+    let (field, token) = parse_query(address, token)?;
 
-    Ok(drop.cumulative_amount)
+    relevant_data
+        .address_token_amount(field, token)
+        .map_err(|e| ApiError::new(ErrorKind::Other, e))
 }
 
-pub fn get_raw_root(round: Option<u8>) -> Result<RootQueryResult, String> {
-    let relevant_data = match get_round_data(round) {
-        Ok(value) => value,
-        Err(value) => return Err(value),
+pub fn get_raw_allocation_claims(
+    round: Option<u8>,
+    visibility: Visibility,
+    address: &str,
+    token: Option<&str>,
+) -> Result<AllocationClaimsResult, ApiError> {
+    let data = get_api_data();
+    let (field, token) = parse_query(address, token)?;
+    let amount = get_round_data(&data, round, visibility)?
+        .address_token_amount(field, token)
+        .map_err(|e| ApiError::new(ErrorKind::Other, e))?;
+
+    // The Distributor only pays out the allocations without a token, so the others are never claimable
+    let claimed = match token {
+        None => data.claims.claimed(&field),
+        Some(_) => 0,
+    };
+    let rounds = served_rounds(&data.rounds, &data.config, Visibility::Served);
+    let claimable_now = match (token, claimable_round(&rounds, &data.config)) {
+        (None, Some(data)) => data
+            .address_amount(field)
+            .map_err(|e| ApiError::new(ErrorKind::Other, e))?
+            .saturating_sub(claimed),
+        _ => 0,
     };
+
+    Ok(AllocationClaimsResult {
+        amount: amount.to_string(),
+        claimed: claimed.to_string(),
+        claimable_now: claimable_now.to_string(),
+    })
+}
+
+/// The rounds that are served, without the drafts
+pub fn get_raw_rounds() -> Vec<RoundSummaryResult> {
+    let data = get_api_data();
+    served_rounds(&data.rounds, &data.config, Visibility::Served)
+        .iter()
+        .map(|round| RoundSummaryResult {
+            round: round.round,
+            slot: round.slot,
+            root: felt_to_b16(&round.tree.root),
+            state: round_state(round, &data.config),
+            accumulated_total_amount: round.accumulated_total_amount.to_string(),
+            round_total_amount: round.round_total_amount.to_string(),
+            claimed_amount: claimed_in_round(round, &data.claims).to_string(),
+            unclaimed_amount: unclaimed_in_round(round, &data.claims).to_string(),
+        })
+        .collect()
+}
+
+pub fn get_raw_root(
+    round: Option<u8>,
+    visibility: Visibility,
+) -> Result<RootQueryResult, ApiError> {
+    let relevant_data = get_round_data(&get_api_data(), round, visibility)?;
     let res = RootQueryResult {
-        root: felt_to_b16(&relevant_data.tree.root.value),
+        root: felt_to_b16(&relevant_data.tree.root),
+        // Only rounds with a slot are served
+        slot: relevant_data.slot.unwrap_or_default(),
         accumulated_total_amount: relevant_data.accumulated_total_amount.to_string(),
         round_total_amount: relevant_data.round_total_amount.to_string(),
+        token_totals: relevant_data
+            .token_totals
+            .iter()
+            .map(|t| TokenTotalsResult {
+                token: format_address(&t.token),
+                accumulated_total_amount: t.accumulated_total_amount.to_string(),
+                round_total_amount: t.round_total_amount.to_string(),
+            })
+            .collect(),
+        exclusions: relevant_data
+            .exclusions
+            .iter()
+            .map(|e| ExclusionResult {
+                address: format_address(&e.address),
+                token: e.token.as_ref().map(format_address),
+                amount: e.amount.to_string(),
+                reason: e.reason.clone(),
+                redistributed: e.redistributed,
+            })
+            .collect(),
+        limits: relevant_data
+            .limits
+            .iter()
+            .map(|l| LimitResult {
+                address: format_address(&l.address),
+                token: l.token.as_ref().map(format_address),
+                kind: l.kind,
+                amount: l.amount.to_string(),
+            })
+            .collect(),
+        burned_amount: relevant_data.burned_amount.to_string(),
     };
     Ok(res)
 }
 
-// Gets data for a specific round
-fn get_round_data(round: Option<u8>) -> Result<RoundTreeData, String> {
-    let round_data = get_all_data();
-    // Use round if it's provided. Otherwise use the latest round
-    let use_round = match round {
-        Some(v) => v,
-        None => match round_data.iter().max_by_key(|&p| p.round) {
-            None => return Err("No allocation data found".to_string()),
-            Some(p) => p.round,
-        },
-    };
-    let relevant_data = round_data.iter().find(|&p| p.round == use_round);
+pub fn get_raw_vesting_schedule(address: &str) -> Result<Vec<VestingRound>, ApiError> {
+    let data = get_api_data();
+    let rounds: Vec<u8> = data.rounds.iter().map(|r| r.round).collect();
+    vesting_preview(&data.vesting_schedules, address, &rounds)
+}
 
-    match relevant_data {
-        Some(data) => Ok(data.clone()),
-        None => Err("No allocation data available".to_string()),
+// Gets a shared handle to the data of a specific round. Drafts are only given in previews
+fn get_round_data(
+    data: &ApiData,
+    round: Option<u8>,
+    visibility: Visibility,
+) -> Result<Arc<RoundTreeData>, ApiError> {
+    let config = &data.config;
+    if let Some(round_data) = round.and_then(|r| data.rounds.iter().find(|d| d.round == r)) {
+        match round_state(round_data, config) {
+            RoundState::Retired => {
+                return Err(ApiError::new(
+                    ErrorKind::RoundNotServed,
+                    "Round has been retired",
+                ))
+            }
+            state if !is_served(state, visibility) => {
+                return Err(ApiError::new(
+                    ErrorKind::RoundNotServed,
+                    "Round hasn't been approved",
+                ))
+            }
+            _ => (),
+        }
     }
+    select_round(&served_rounds(&data.rounds, config, visibility), round).cloned()
+}
+
+pub fn get_raw_publication_plan() -> PublicationPlan {
+    let data = get_api_data();
+    publication_plan(&data.rounds, &data.config)
 }
 
 /// Converts JSON allocation data into cumulative tree+data per round
+#[instrument(skip_all, fields(rounds = allocations.len()))]
 pub fn transform_allocations_to_cumulative_rounds(
     mut allocations: Vec<RoundAmounts>,
+    config: &CampaignConfig,
 ) -> Vec<RoundTreeData> {
-    if allocations.len() == 0 {
+    if allocations.is_empty() {
         return Vec::new();
     }
-    allocations.sort_by(|a, b| a.round.cmp(&b.round));
+    allocations.sort_by_key(|a| a.round);
 
-    let cumulative_amount_maps = map_cumulative_amounts(allocations);
+    let cumulative_amount_maps = map_cumulative_amounts(allocations, config);
 
     let mut accumulated_total_amount = 0_u128;
+    let mut accumulated_token_amounts: HashMap<FieldElement, u128> = HashMap::new();
 
     let mut rounds: Vec<RoundTreeData> = Vec::new();
     for cum_map in cumulative_amount_maps.iter() {
         let mut curr_round_data: Vec<CumulativeAllocation> = Vec::new();
         let mut round_total_amount = 0_u128;
+        let mut round_token_amounts: HashMap<FieldElement, u128> = HashMap::new();
 
         // The cumulative map has all addresses that have any allocation in this round and all previous rounds
         for key in cum_map.cumulative_amounts.keys() {
+            let (address, token) = *key;
             let address_cumulative = CumulativeAllocation {
-                address: *key,
+                address,
                 cumulative_amount: cum_map.cumulative_amounts[key],
+                token,
+                extra_fields: cum_map.extra_fields.get(key).cloned().unwrap_or_default(),
             };
-            // If this round has this address add its amount to the round total amount
-            if cum_map.round_amounts.contains_key(key) {
-                round_total_amount += cum_map.round_amounts[key];
+            // If this round has this address add its amount to the total of its token.
+            // The overall totals only have the amounts without a token, so units aren't mixed
+            if let Some(amount) = cum_map.round_amounts.get(key) {
+                match token {
+                    Some(token) => {
+                        *round_token_amounts.entry(token).or_insert(0) += amount;
+                        *accumulated_token_amounts.entry(token).or_insert(0) += amount;
+                    }
+                    None => round_total_amount += amount,
+                }
             }
             curr_round_data.push(address_cumulative);
         }
         accumulated_total_amount += round_total_amount;
 
-        if curr_round_data.len() > 0 {
-            // Sort because hashmap iterator returns keys in arbitrary order
-            curr_round_data.sort_by(|a, b| a.address.cmp(&b.address));
+        let mut token_totals: Vec<TokenTotals> = accumulated_token_amounts
+            .iter()
+            .map(|(token, accumulated)| TokenTotals {
+                token: *token,
+                accumulated_total_amount: *accumulated,
+                round_total_amount: round_token_amounts.get(token).copied().unwrap_or(0),
+            })
+            .collect();
+        token_totals.sort_by_key(|t| t.token);
 
-            let tree = MerkleTree::new(curr_round_data);
+        if !curr_round_data.is_empty() {
+            let _span = info_span!("build_round", round = cum_map.round).entered();
+
+            let leaf_count = curr_round_data.len();
+            let start = Instant::now();
+            let tree = MerkleTree::with_hasher(curr_round_data, config.hash_scheme);
+            record_round_build(cum_map.round, leaf_count, start.elapsed().as_millis());
 
             let round_drop = RoundTreeData {
                 round: cum_map.round,
-                tree: tree,
-                accumulated_total_amount: accumulated_total_amount,
-                round_total_amount: round_total_amount,
+                slot: None,
+                tree,
+                accumulated_total_amount,
+                round_total_amount,
+                token_totals,
+                exclusions: cum_map.exclusions.clone(),
+                limits: cum_map.limits.clone(),
+                burned_amount: cum_map.burned_amount,
             };
 
-            println!(
-                "Extracted data from round {:?}: 
-                Round total token amount: {:?}, 
-                Cumulative token amount: {:?}",
-                cum_map.round, round_drop.round_total_amount, round_drop.accumulated_total_amount
+            info!(
+                round = cum_map.round,
+                round_total_amount = %round_drop.round_total_amount,
+                accumulated_total_amount = %round_drop.accumulated_total_amount,
+                "Extracted data from round"
             );
 
             rounds.push(round_drop);
         }
     }
+    assign_slots(&mut rounds, config.first_round);
     rounds
 }
 
-/// Converts JSON allocation data into cumulative map-per-round data
-pub fn map_cumulative_amounts(allocations: Vec<RoundAmounts>) -> Vec<RoundAmountMaps> {
-    let mut all_rounds_cums: HashMap<FieldElement, u128> = HashMap::new();
+/// Converts JSON allocation data into cumulative map-per-round data.
+/// Allocations of denied addresses are removed and handled according to the campaign's exclusion policy,
+/// then the caps and the minimum claim of the campaign are applied
+#[instrument(name = "accumulate", skip_all)]
+pub fn map_cumulative_amounts(
+    allocations: Vec<RoundAmounts>,
+    config: &CampaignConfig,
+) -> Vec<RoundAmountMaps> {
+    let denylist = Denylist::new(&config.denylist);
+    let mut limit_state = LimitState::default();
+    let mut all_rounds_cums: HashMap<AllocationKey, u128> = HashMap::new();
+    let mut all_rounds_extra_fields: HashMap<AllocationKey, Vec<FieldElement>> = HashMap::new();
     let mut round_maps: Vec<RoundAmountMaps> = Vec::new();
 
     for allocation in allocations.iter() {
-        let mut curr_round_amounts: HashMap<FieldElement, u128> = HashMap::new();
+        let mut curr_round_amounts: HashMap<AllocationKey, u128> = HashMap::new();
+        let mut excluded_amounts: HashMap<AllocationKey, u128> = HashMap::new();
 
         for data in allocation.amounts.iter() {
             let amount = match data.amount.parse::<u128>() {
@@ -155,15 +323,95 @@ pub fn map_cumulative_amounts(allocations: Vec<RoundAmounts>) -> Vec<RoundAmount
                 Err(_) => 0_u128, // If number is invalid assign 0
             };
 
-            let field = FieldElement::from_str(&data.address).unwrap();
+            // Malformed rows are skipped, so one bad row doesn't stop the whole load
+            let field = match Address::from_str(&data.address) {
+                Ok(address) => address.felt(),
+                Err(e) => {
+                    warn!(
+                        round = allocation.round,
+                        address = ?data.address,
+                        error = %e,
+                        "Skipped row with an invalid address"
+                    );
+                    continue;
+                }
+            };
+            let token = match parse_optional_address(data.token.as_deref()) {
+                Ok(token) => token,
+                Err(e) => {
+                    warn!(
+                        round = allocation.round,
+                        token = ?data.token,
+                        error = %e,
+                        "Skipped row with an invalid token"
+                    );
+                    continue;
+                }
+            };
+            // Each token of an address is accumulated separately and gets its own leaf
+            let key = (field, token);
+
+            let extra_fields: Vec<FieldElement> = match data
+                .extra_fields
+                .iter()
+                .map(|f| FieldElement::from_str(f))
+                .collect()
+            {
+                Ok(fields) => fields,
+                Err(_) => {
+                    warn!(
+                        round = allocation.round,
+                        address = %format_address(&field),
+                        "Skipped row with an invalid extra field"
+                    );
+                    continue;
+                }
+            };
+
+            if denylist.denies(&field, allocation.round).is_some() {
+                *excluded_amounts.entry(key).or_insert_with(|| 0) += amount;
+                continue;
+            }
+
+            *curr_round_amounts.entry(key).or_insert_with(|| 0) += amount;
+
+            // The latest given extra fields are used. Rows without them keep the previous ones
+            if !extra_fields.is_empty() {
+                all_rounds_extra_fields.insert(key, extra_fields);
+            }
+        }
 
-            *curr_round_amounts.entry(field).or_insert_with(|| 0) += amount;
-            *all_rounds_cums.entry(field).or_insert_with(|| 0) += amount;
+        let exclusions = apply_exclusions(
+            allocation.round,
+            &mut curr_round_amounts,
+            &excluded_amounts,
+            &denylist,
+            config.exclusion_policy,
+        );
+        let (limits, burned_amount) = apply_limits(
+            allocation.round,
+            &mut curr_round_amounts,
+            &mut limit_state,
+            config,
+        );
+        for (key, amount) in curr_round_amounts.iter() {
+            *all_rounds_cums.entry(*key).or_insert_with(|| 0) += amount;
         }
+
+        debug!(
+            round = allocation.round,
+            leaves = curr_round_amounts.len(),
+            cumulative_leaves = all_rounds_cums.len(),
+            "Accumulated round"
+        );
         let map = RoundAmountMaps {
             round: allocation.round,
             round_amounts: curr_round_amounts,
             cumulative_amounts: all_rounds_cums.clone(),
+            extra_fields: all_rounds_extra_fields.clone(),
+            exclusions,
+            limits,
+            burned_amount,
         };
 
         round_maps.push(map);
@@ -174,22 +422,52 @@ pub fn map_cumulative_amounts(allocations: Vec<RoundAmounts>) -> Vec<RoundAmount
 
 // Reads and accumulates all allocation info for all rounds
 pub fn read_allocations(filepath: String) -> Vec<RoundTreeData> {
+    let config = read_campaign_config(&filepath);
+    let schedules = read_vesting_schedules(&filepath);
+    build_allocations(filepath, &config, &schedules)
+}
+
+/// Reads and accumulates the input files with the settings and vesting schedules already read from the folder
+#[instrument(skip_all, fields(path = %filepath))]
+pub fn build_allocations(
+    filepath: String,
+    config: &CampaignConfig,
+    schedules: &[VestingSchedule],
+) -> Vec<RoundTreeData> {
+    let start = Instant::now();
+    let mut round_amounts = read_round_files(filepath);
+
+    // Vested amounts are released like any other allocation of their round
+    let merge = merge_vesting_rounds(&mut round_amounts, schedules);
+    for (index, e) in merge.invalid.iter() {
+        error!(schedule = index, "Skipped invalid vesting schedule: {}", e);
+    }
+    if !merge.pending.is_empty() {
+        let rounds: BTreeSet<u8> = merge.pending.iter().map(|(_, round, _)| *round).collect();
+        info!(
+            tranches = merge.pending.len(),
+            rounds = ?rounds,
+            "Vesting tranches of rounds without an input file aren't released yet"
+        );
+    }
+
+    let rounds = transform_allocations_to_cumulative_rounds(round_amounts, config);
+    record_allocation_read(start.elapsed().as_millis());
+    rounds
+}
+
+/// Reads the rows of each input file of the folder, without the vested amounts
+pub fn read_round_files(filepath: String) -> Vec<RoundAmounts> {
     let files = retrieve_valid_files(filepath);
     let mut round_amounts: Vec<RoundAmounts> = vec![];
 
     for file in files.iter() {
+        let _span = info_span!("parse_file", round = file.round, file = %file.full_path).entered();
         let zipfile = File::open(file.clone().full_path).expect("Failed to open zip file");
-        let mut archive: zip::ZipArchive<File> = ZipArchive::<File>::new(zipfile).unwrap();
-        if archive.len() > 0 {
-            // Only read the first file in the zip archive
-            let mut archive_file = archive.by_index(0).unwrap();
-            let mut buffer = Vec::new();
-            archive_file
-                .read_to_end(&mut buffer)
-                .expect("problem reading zip");
-
-            let allocation: Vec<JSONAllocation> =
-                from_slice(&buffer).expect("Failed to deserialize allocation");
+        let allocations = read_zip_allocations(zipfile)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", file.full_path, e));
+        if let Some(allocation) = allocations {
+            debug!(rows = allocation.len(), "Parsed input file");
 
             let round_amount = RoundAmounts {
                 amounts: allocation.clone(),
@@ -198,54 +476,102 @@ pub fn read_allocations(filepath: String) -> Vec<RoundTreeData> {
             round_amounts.push(round_amount);
         }
     }
-    transform_allocations_to_cumulative_rounds(round_amounts)
+    round_amounts
+}
+
+/// Reads the allocations from the first file of a zip archive. None if the archive is empty
+pub fn read_zip_allocations<R: Read + Seek>(
+    reader: R,
+) -> Result<Option<Vec<JSONAllocation>>, String> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| e.to_string())?;
+    if archive.is_empty() {
+        return Ok(None);
+    }
+    // Only read the first file in the zip archive
+    let mut archive_file = archive.by_index(0).map_err(|e| e.to_string())?;
+    let mut buffer = Vec::new();
+    archive_file
+        .read_to_end(&mut buffer)
+        .map_err(|e| e.to_string())?;
+    parse_allocations(&buffer).map(Some)
+}
+
+/// Parses the JSON rows of an input file
+pub fn parse_allocations(contents: &[u8]) -> Result<Vec<JSONAllocation>, String> {
+    from_slice(contents).map_err(|e| e.to_string())
+}
+
+/// Reads the campaign settings from campaign.json in the input folder. Uses the defaults if there's no such file.
+/// The denylist, the published roots and the round states come from their own files
+pub fn read_campaign_config(filepath: &str) -> CampaignConfig {
+    let path = Path::new(filepath).join("campaign.json");
+    let mut config = match path.exists() {
+        true => {
+            let contents = fs::read(&path).expect("Failed to read campaign config");
+            let config: CampaignConfig =
+                from_slice(&contents).expect("Failed to deserialize campaign config");
+            info!(hash_scheme = ?config.hash_scheme, exclusion_policy = ?config.exclusion_policy, "Read campaign config");
+            config
+        }
+        false => CampaignConfig::default(),
+    };
+    config.denylist = read_denylist(filepath);
+    config.published_roots = read_published_roots(filepath);
+    config.round_states = read_round_states(filepath);
+    config
 }
 
 /// Returns all files that have the correct filename syntax
+#[instrument(name = "discover_files", skip_all, fields(path = %filepath))]
 pub fn retrieve_valid_files(filepath: String) -> Vec<FileNameInfo> {
     let mut valid_files: Vec<FileNameInfo> = vec![];
     let path = Path::new(&filepath);
 
     // Case in-sensitive, find pattern
     let template_pattern = r"(?i)^raw_(\d+)\.zip$";
-    let regex = Regex::new(&template_pattern).expect("Invalid regex pattern");
-
-    for entry in path.read_dir().expect("read_dir call failed") {
-        if let Ok(entry) = entry {
-            if let Some(captures) = regex.captures(entry.file_name().to_str().unwrap()) {
-                // Collect valid file names
-                if let Some(round) = captures.get(1) {
-                    // Don't allow 0 round
-                    if round.as_str() != "0".to_string() {
-                        let fileinfo = FileNameInfo {
-                            full_path: entry.path().to_str().unwrap().to_string(),
-                            round: round.as_str().parse::<u8>().unwrap(),
-                        };
-                        valid_files.push(fileinfo);
-                    }
+    let regex = Regex::new(template_pattern).expect("Invalid regex pattern");
+
+    for entry in path.read_dir().expect("read_dir call failed").flatten() {
+        if let Some(captures) = regex.captures(entry.file_name().to_str().unwrap()) {
+            // Collect valid file names
+            if let Some(round) = captures.get(1) {
+                // Don't allow 0 round
+                if round.as_str() != "0" {
+                    let fileinfo = FileNameInfo {
+                        full_path: entry.path().to_str().unwrap().to_string(),
+                        round: round.as_str().parse::<u8>().unwrap(),
+                    };
+                    debug!(round = fileinfo.round, file = %fileinfo.full_path, "Found input file");
+                    valid_files.push(fileinfo);
                 }
             }
         }
     }
-    println!("Found {} valid input files", valid_files.len());
+    info!(count = valid_files.len(), "Found valid input files");
     valid_files
 }
 
 impl RoundTreeData {
+    /// Rough estimate of the memory used by this round's data, in bytes
+    pub fn estimated_size(&self) -> usize {
+        size_of::<RoundTreeData>() - size_of::<MerkleTree>() + self.tree.estimated_size()
+    }
+
     /// Retrieve allocated amount for an address in a specific round
     pub fn address_amount(&self, address: FieldElement) -> Result<u128, String> {
-        let address_drop: Vec<CumulativeAllocation> = self
-            .tree
-            .allocations
-            .iter()
-            .filter(|a| a.address == address)
-            .cloned()
-            .collect();
+        self.address_token_amount(address, None)
+    }
 
-        if address_drop.len() == 0 {
-            Ok(0_u128)
-        } else {
-            Ok(address_drop.get(0).unwrap().cumulative_amount)
+    /// Retrieve allocated amount for an address and token in a specific round.
+    /// Without a token it's the allocation without a token
+    pub fn address_token_amount(
+        &self,
+        address: FieldElement,
+        token: Option<FieldElement>,
+    ) -> Result<u128, String> {
+        match self.tree.find_leaf(&address, token.as_ref()) {
+            Some(index) => Ok(self.tree.allocations[index].cumulative_amount),
+            None => Ok(0_u128),
         }
     }
 }
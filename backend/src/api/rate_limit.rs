@@ -0,0 +1,392 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::RETRY_AFTER,
+    web, Error, HttpResponse,
+};
+use serde::Deserialize;
+use serde_json::from_slice;
+use std::{
+    collections::HashMap,
+    env, fs,
+    future::{ready, Future, Ready},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use super::metrics::record_rejection;
+
+/// Per-IP buckets are dropped once they are full again if more than this many are tracked
+const MAX_TRACKED_BUCKETS: usize = 100_000;
+/// How many new per-IP buckets are added between two sweeps of the full ones
+const SWEEP_INTERVAL: usize = 10_000;
+
+/// A token bucket: requests take a token, and tokens come back at a steady rate up to the burst size
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    /// Tokens added per second
+    pub rate: f64,
+    /// Most tokens the bucket holds, i.e. the most requests allowed at once
+    pub burst: f64,
+}
+
+/// Limits of one endpoint. Limits that aren't given are the default ones
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct EndpointLimits {
+    pub per_ip: Option<BucketConfig>,
+    pub global: Option<BucketConfig>,
+}
+
+/// Read from the file in RATE_LIMITS_FILE, or rate_limits.json by default
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Default limit of each client IP for each endpoint
+    pub per_ip: Option<BucketConfig>,
+    /// Default limit of all clients together for each endpoint
+    pub global: Option<BucketConfig>,
+    /// Limits by endpoint path, e.g. "/get_multiproof_calldata"
+    pub endpoints: HashMap<String, EndpointLimits>,
+    pub max_query_length: usize,
+    /// Most addresses in one multiproof request
+    pub max_batch_size: usize,
+    /// Trusted internal callers that aren't limited
+    pub allowlist: Vec<IpAddr>,
+    /// Take the client IP from the Forwarded or X-Forwarded-For header set by the reverse proxy.
+    /// Only safe if the backend can't be reached without the proxy
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_ip: Some(BucketConfig {
+                rate: 10.0,
+                burst: 20.0,
+            }),
+            global: Some(BucketConfig {
+                rate: 1000.0,
+                burst: 2000.0,
+            }),
+            endpoints: HashMap::new(),
+            max_query_length: 4096,
+            max_batch_size: 100,
+            allowlist: vec![],
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let file =
+            env::var("RATE_LIMITS_FILE").unwrap_or_else(|_| "./rate_limits.json".to_string());
+        read_rate_limits(&file)
+    }
+
+    fn per_ip(&self, endpoint: &str) -> Option<BucketConfig> {
+        match self.endpoints.get(endpoint).and_then(|e| e.per_ip) {
+            Some(v) => Some(v),
+            None => self.per_ip,
+        }
+    }
+
+    fn global(&self, endpoint: &str) -> Option<BucketConfig> {
+        match self.endpoints.get(endpoint).and_then(|e| e.global) {
+            Some(v) => Some(v),
+            None => self.global,
+        }
+    }
+}
+
+/// Reads the rate limits from a file. Uses the defaults if there's no such file
+pub fn read_rate_limits(filepath: &str) -> RateLimitConfig {
+    let path = Path::new(filepath);
+    if !path.exists() {
+        return RateLimitConfig::default();
+    }
+    let contents = fs::read(path).expect("Failed to read rate limits");
+    let config: RateLimitConfig = from_slice(&contents).expect("Failed to deserialize rate limits");
+    info!(
+        endpoints = config.endpoints.len(),
+        allowlist = config.allowlist.len(),
+        "Read rate limits"
+    );
+    config
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(config: &BucketConfig, now: Instant) -> Self {
+        TokenBucket {
+            tokens: config.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.rate).min(config.burst);
+        self.updated = now;
+    }
+
+    /// Checks that there is a token, without taking it. Otherwise returns how long until there is
+    fn check(&mut self, config: &BucketConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            return Ok(());
+        }
+        match config.rate > 0.0 {
+            true => Err(Duration::from_secs_f64((1.0 - self.tokens) / config.rate)),
+            false => Err(Duration::MAX),
+        }
+    }
+}
+
+/// Why a request was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    QueryTooLong,
+    BatchTooLarge(usize),
+    /// Over the limit of the client IP. Holds the time until the next request is allowed
+    PerIpLimit(Duration),
+    /// Over the limit of all clients together
+    GlobalLimit(Duration),
+}
+
+impl Rejection {
+    fn kind(&self) -> &'static str {
+        match self {
+            Rejection::QueryTooLong => "query_too_long",
+            Rejection::BatchTooLarge(_) => "batch_too_large",
+            Rejection::PerIpLimit(_) => "per_ip_limit",
+            Rejection::GlobalLimit(_) => "global_limit",
+        }
+    }
+
+    fn response(&self) -> HttpResponse {
+        match self {
+            Rejection::QueryTooLong => HttpResponse::UriTooLong().json("Query string is too long"),
+            Rejection::BatchTooLarge(max) => HttpResponse::BadRequest()
+                .json(format!("Too many addresses, at most {} are allowed", max)),
+            Rejection::PerIpLimit(wait) | Rejection::GlobalLimit(wait) => {
+                // Whole seconds, at least 1
+                let seconds = wait.as_secs().saturating_add(1).min(86400);
+                HttpResponse::TooManyRequests()
+                    .insert_header((RETRY_AFTER, seconds.to_string()))
+                    .json("Too many requests")
+            }
+        }
+    }
+}
+
+/// The per-IP buckets of all endpoints
+#[derive(Debug, Default)]
+struct IpBuckets {
+    buckets: HashMap<(String, IpAddr), TokenBucket>,
+    /// New buckets since the last sweep
+    added: usize,
+}
+
+/// The buckets of all endpoints, shared by all workers
+#[derive(Debug)]
+pub struct RateLimitState {
+    config: RateLimitConfig,
+    per_ip: Mutex<IpBuckets>,
+    global: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimitState {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimitState {
+            config,
+            per_ip: Mutex::new(IpBuckets::default()),
+            global: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks a request to an endpoint and takes its tokens.
+    /// Tokens are only taken if both the IP and the global limit allow the request
+    pub fn check(
+        &self,
+        endpoint: &str,
+        ip: Option<IpAddr>,
+        query: &str,
+        now: Instant,
+    ) -> Result<(), Rejection> {
+        if ip.is_some_and(|ip| self.config.allowlist.contains(&ip)) {
+            return Ok(());
+        }
+        if query.len() > self.config.max_query_length {
+            return Err(Rejection::QueryTooLong);
+        }
+        if batch_size(query) > self.config.max_batch_size {
+            return Err(Rejection::BatchTooLarge(self.config.max_batch_size));
+        }
+
+        // Always locked in this order. The IP limit goes first, so a single client can't use up the global limit
+        let mut ip_buckets = self.per_ip.lock().expect("Failed to acquire lock");
+        let mut global_buckets = self.global.lock().expect("Failed to acquire lock");
+        let ip_bucket = match (self.config.per_ip(endpoint), ip) {
+            (Some(config), Some(ip)) => {
+                let bucket = self.ip_bucket(&mut ip_buckets, endpoint, ip, &config, now);
+                bucket.check(&config, now).map_err(Rejection::PerIpLimit)?;
+                Some(bucket)
+            }
+            _ => None,
+        };
+        let global_bucket = match self.config.global(endpoint) {
+            Some(config) => {
+                let bucket = global_buckets
+                    .entry(endpoint.to_string())
+                    .or_insert_with(|| TokenBucket::full(&config, now));
+                bucket.check(&config, now).map_err(Rejection::GlobalLimit)?;
+                Some(bucket)
+            }
+            None => None,
+        };
+
+        for bucket in [ip_bucket, global_bucket].into_iter().flatten() {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    /// The bucket of an IP for an endpoint. Full buckets are dropped every SWEEP_INTERVAL new buckets
+    /// once too many are tracked, so the sweep doesn't run on every request
+    fn ip_bucket<'a>(
+        &self,
+        ip_buckets: &'a mut IpBuckets,
+        endpoint: &str,
+        ip: IpAddr,
+        config: &BucketConfig,
+        now: Instant,
+    ) -> &'a mut TokenBucket {
+        let key = (endpoint.to_string(), ip);
+        if !ip_buckets.buckets.contains_key(&key) {
+            ip_buckets.added += 1;
+            if ip_buckets.added >= SWEEP_INTERVAL && ip_buckets.buckets.len() > MAX_TRACKED_BUCKETS
+            {
+                ip_buckets.buckets.retain(|(e, _), bucket| {
+                    let config = self.config.per_ip(e).unwrap_or(*config);
+                    bucket.refill(&config, now);
+                    bucket.tokens < config.burst
+                });
+                ip_buckets.added = 0;
+            }
+        }
+        ip_buckets
+            .buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::full(config, now))
+    }
+}
+
+/// Number of addresses in the addresses parameter of a multiproof request
+fn batch_size(query: &str) -> usize {
+    match web::Query::<HashMap<String, String>>::from_query(query) {
+        Ok(params) => params
+            .get("addresses")
+            .map(|a| a.split(',').filter(|a| !a.trim().is_empty()).count())
+            .unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+/// Middleware rejecting requests over the rate limits, with too long query strings or too large batches
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: Arc<RateLimitState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            state: Arc::new(RateLimitState::new(config)),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service,
+            state: self.state.clone(),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: S,
+    state: Arc<RateLimitState>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Use the route pattern so that unknown paths share their limits
+        let endpoint = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let ip = match self.state.config.trust_forwarded_for {
+            true => req
+                .connection_info()
+                .realip_remote_addr()
+                .and_then(parse_ip),
+            false => req.peer_addr().map(|a| a.ip()),
+        };
+
+        match self
+            .state
+            .check(&endpoint, ip, req.query_string(), Instant::now())
+        {
+            Ok(()) => {
+                let fut = self.service.call(req);
+                Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+            }
+            Err(rejection) => {
+                warn!(endpoint = %endpoint, reason = rejection.kind(), "Request rejected");
+                record_rejection(&endpoint, rejection.kind());
+                let res = req.into_response(rejection.response());
+                Box::pin(async move { Ok(res.map_into_right_body()) })
+            }
+        }
+    }
+}
+
+/// Parses an IP with or without a port
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|a| a.ip()))
+}
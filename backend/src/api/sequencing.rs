@@ -0,0 +1,172 @@
+use serde_json::from_slice;
+use starknet_crypto::FieldElement;
+use std::{borrow::Borrow, fs, path::Path, str::FromStr};
+use tracing::{info, warn};
+
+use super::{
+    errors::{ApiError, ErrorKind},
+    lifecycle::{is_served, round_state},
+    merkle_tree::felt_to_b16,
+    structs::{
+        CampaignConfig, PublicationPlan, PublicationStatus, PublicationStep, RoundTreeData,
+        Visibility,
+    },
+};
+
+/// Maps the rounds to the slots the contract stores their roots in.
+/// The first round of the campaign goes to slot 0 and each following round to the next slot.
+/// Rounds after a missing round get no slot, as their roots can't be added before the missing one
+pub fn assign_slots(rounds: &mut [RoundTreeData], first_round: Option<u8>) {
+    rounds.sort_by_key(|r| r.round);
+    let first_round = match first_round.or(rounds.first().map(|r| r.round)) {
+        Some(v) => v,
+        None => return,
+    };
+
+    let mut expected = Some(first_round);
+    let mut slot = 0_u64;
+    for round in rounds.iter_mut() {
+        if round.round < first_round {
+            round.slot = None;
+        } else if Some(round.round) == expected {
+            round.slot = Some(slot);
+            slot += 1;
+            expected = round.round.checked_add(1);
+        } else {
+            round.slot = None;
+            // Only the rounds before the first gap get slots
+            expected = None;
+            warn!(round = round.round, "Round has missing predecessors");
+        }
+    }
+}
+
+/// Rounds between the first round of the campaign and the latest round that have no data
+pub fn missing_rounds<R: Borrow<RoundTreeData>>(rounds: &[R], first_round: Option<u8>) -> Vec<u8> {
+    let present: Vec<u8> = rounds.iter().map(|r| round_data(r).round).collect();
+    let first_round = match first_round.or(present.iter().min().copied()) {
+        Some(v) => v,
+        None => return vec![],
+    };
+    let last_round = present.iter().max().copied().unwrap_or(first_round);
+
+    (first_round..=last_round)
+        .filter(|round| !present.contains(round))
+        .collect()
+}
+
+/// Picks the given round, or the latest round if none is given.
+/// Rounds without a slot aren't served, so a root is never used before its predecessors can be published
+pub fn select_round<R: Borrow<RoundTreeData>>(
+    rounds: &[R],
+    round: Option<u8>,
+) -> Result<&R, ApiError> {
+    // Use round if it's provided. Otherwise use the latest round that can be served
+    let use_round = match round {
+        Some(v) => v,
+        None => match rounds
+            .iter()
+            .map(round_data)
+            .filter(|r| r.slot.is_some())
+            .max_by_key(|r| r.round)
+        {
+            None => {
+                return Err(ApiError::new(
+                    ErrorKind::RoundNotFound,
+                    "No allocation data found",
+                ))
+            }
+            Some(r) => r.round,
+        },
+    };
+
+    match rounds.iter().find(|r| round_data(*r).round == use_round) {
+        Some(data) if round_data(data).slot.is_none() => Err(ApiError::new(
+            ErrorKind::RoundNotSequenced,
+            "Round can't be served before all of its predecessors",
+        )),
+        Some(data) => Ok(data),
+        None => Err(ApiError::new(
+            ErrorKind::RoundNotFound,
+            "No allocation data available",
+        )),
+    }
+}
+
+/// The data of a round, whether the rounds are stored as they are or shared with Arc
+fn round_data<R: Borrow<RoundTreeData>>(round: &R) -> &RoundTreeData {
+    round.borrow()
+}
+
+/// Compares the slots of the served rounds to the roots already added to the contract.
+/// The pending roots have to be added with add_root in the order of their slots. Drafts aren't listed until they're approved
+pub fn publication_plan<R: Borrow<RoundTreeData>>(
+    rounds: &[R],
+    config: &CampaignConfig,
+) -> PublicationPlan {
+    let published = &config.published_roots;
+
+    let mut steps: Vec<PublicationStep> = rounds
+        .iter()
+        .map(round_data)
+        .filter(|r| is_served(round_state(r, config), Visibility::Served))
+        .filter_map(|r| r.slot.map(|slot| (r, slot)))
+        .map(|(r, slot)| {
+            let status = match published.get(slot as usize) {
+                None => PublicationStatus::Pending,
+                Some(root) if *root == r.tree.root => PublicationStatus::Published,
+                Some(_) => PublicationStatus::Mismatch,
+            };
+            PublicationStep {
+                round: r.round,
+                slot,
+                root: felt_to_b16(&r.tree.root),
+                status,
+            }
+        })
+        .collect();
+    steps.sort_by_key(|s| s.slot);
+
+    PublicationPlan {
+        next_free_slot: published.len() as u64,
+        steps,
+        missing_rounds: missing_rounds(rounds, config.first_round),
+    }
+}
+
+/// The latest round whose root is in the contract, which is what can be claimed now.
+/// The latest round that can be served if the published roots aren't known
+pub fn claimable_round<'a, R: Borrow<RoundTreeData>>(
+    rounds: &'a [R],
+    config: &CampaignConfig,
+) -> Option<&'a R> {
+    if config.published_roots.is_empty() {
+        return select_round(rounds, None).ok();
+    }
+    rounds
+        .iter()
+        .filter(|r| {
+            let r = round_data(*r);
+            match r.slot {
+                Some(slot) => config.published_roots.get(slot as usize) == Some(&r.tree.root),
+                None => false,
+            }
+        })
+        .max_by_key(|r| round_data(*r).round)
+}
+
+/// Reads the roots already added to the contract from published_roots.json in the input folder, in slot order.
+/// Empty if there's no such file
+pub fn read_published_roots(filepath: &str) -> Vec<FieldElement> {
+    let path = Path::new(filepath).join("published_roots.json");
+    if !path.exists() {
+        return Vec::new();
+    }
+    let contents = fs::read(&path).expect("Failed to read published roots");
+    let roots: Vec<String> = from_slice(&contents).expect("Failed to deserialize published roots");
+    info!(roots = roots.len(), "Read published roots");
+    roots
+        .iter()
+        .map(|r| FieldElement::from_str(r).expect("Invalid published root"))
+        .collect()
+}
@@ -1,22 +1,55 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use starknet_crypto::FieldElement;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+};
 use utoipa::ToSchema;
 
+use super::{
+    exclusions::ExclusionPolicy, hashing::HashScheme, limits::CapPolicy, vesting::VestingCurve,
+};
+
 /// Contains all data used in one round
 #[derive(Debug, Clone)]
 pub struct RoundTreeData {
     /// Which round
     pub round: u8,
+    /// The slot of the contract the round's root goes to. None if an earlier round is missing
+    pub slot: Option<u64>,
     /// Cumulative amounts for each address in a Merkle tree
     pub tree: MerkleTree,
-    /// The accumulated amount of tokens to be distributed in a round. Includes amounts from all previous rounds
+    /// The accumulated amount of tokens to be distributed in a round. Includes amounts from all previous rounds.
+    /// Only allocations without a token, the others are in the token totals
+    pub accumulated_total_amount: u128,
+    /// The total amount of tokens to be distributed in a round. Includes amounts only from one round.
+    /// Only allocations without a token
+    pub round_total_amount: u128,
+    /// Totals of each token given in the input rows, sorted by token. Rows without a token aren't included
+    pub token_totals: Vec<TokenTotals>,
+    /// Allocations of this round removed because of the denylist
+    pub exclusions: Vec<ExclusionRecord>,
+    /// Allocations of this round changed by the caps or the minimum claim
+    pub limits: Vec<LimitRecord>,
+    /// Capped amount of this round that wasn't given to anyone
+    pub burned_amount: u128,
+}
+
+/// Accumulated totals of one token in a round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenTotals {
+    pub token: FieldElement,
+    /// Includes amounts from all previous rounds
     pub accumulated_total_amount: u128,
-    /// The total amount of tokens to be distributed in a round. Includes amounts only from one round
+    /// Includes amounts only from one round
     pub round_total_amount: u128,
 }
 
+/// Identifies one leaf: the address and the optional token of an allocation
+pub type AllocationKey = (FieldElement, Option<FieldElement>);
+
 /// Used for some intermediary calculations
+#[derive(Debug, Clone)]
 pub struct RoundAmounts {
     pub round: u8,
     pub amounts: Vec<JSONAllocation>,
@@ -25,15 +58,32 @@ pub struct RoundAmounts {
 /// Temporary storage inside processing
 pub struct RoundAmountMaps {
     pub round: u8,
-    pub round_amounts: HashMap<FieldElement, u128>,
-    pub cumulative_amounts: HashMap<FieldElement, u128>,
+    pub round_amounts: HashMap<AllocationKey, u128>,
+    pub cumulative_amounts: HashMap<AllocationKey, u128>,
+    /// The latest extra leaf fields given for each address and token in this and previous rounds
+    pub extra_fields: HashMap<AllocationKey, Vec<FieldElement>>,
+    /// Allocations of this round removed because of the denylist
+    pub exclusions: Vec<ExclusionRecord>,
+    /// Allocations of this round changed by the caps or the minimum claim
+    pub limits: Vec<LimitRecord>,
+    /// Capped amount of this round that wasn't given to anyone
+    pub burned_amount: u128,
 }
 
-/// A Merkle tree with extra allocation data for easier access
+/// A Merkle tree with extra allocation data for easier access.
+/// Generic over how the leaves and nodes are hashed
 #[derive(Debug, Clone)]
-pub struct MerkleTree {
-    pub root: Node,
+pub struct MerkleTree<H = HashScheme> {
+    /// The Merkle root
+    pub root: FieldElement,
+    /// Hashes of each level of the tree, leaves first and root last
+    pub levels: Vec<Vec<FieldElement>>,
+    /// Allocations in the same order as the leaves, i.e. sorted by address and token
     pub allocations: Vec<CumulativeAllocation>,
+    /// Positions of the leaves of each address. The leaves of an address are next to each other
+    pub address_index: HashMap<FieldElement, Range<usize>>,
+    /// Hashes the leaves and nodes
+    pub hasher: H,
 }
 
 /// Calldata to be used for the associated Cairo contract
@@ -43,6 +93,27 @@ pub struct CairoCalldata {
     pub amount: String,
     /// Merkle proof for the claim
     pub proof: Vec<String>,
+    /// The token of the claimed leaf, if the allocation has one
+    pub token: Option<String>,
+    /// Extra values hashed into the leaf after the address, amount and token
+    pub extra_fields: Vec<String>,
+}
+
+/// Calldata for claiming several leaves with one combined proof
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CairoMultiproofCalldata {
+    /// The addresses of the leaves, in the order they are consumed when verifying
+    pub addresses: Vec<String>,
+    /// The amount to claim for each address
+    pub amounts: Vec<String>,
+    /// The token of each leaf, if the allocation has one
+    pub tokens: Vec<Option<String>>,
+    /// Extra values hashed into the leaf of each address
+    pub extra_fields: Vec<Vec<String>>,
+    /// Hashes needed in addition to the leaves to compute the root
+    pub proof: Vec<String>,
+    /// One flag per computed hash. True if its second input is a leaf or a computed hash, false if it comes from the proof
+    pub proof_flags: Vec<bool>,
 }
 
 /// Result for querying root data
@@ -50,33 +121,316 @@ pub struct CairoCalldata {
 pub struct RootQueryResult {
     /// The Merkle root for this round
     pub root: String,
-    /// The accumulated amount of tokens to be distributed in a round. Includes amounts from all previous rounds
+    /// The slot of the contract the root goes to
+    pub slot: u64,
+    /// The accumulated amount of tokens to be distributed in a round. Includes amounts from all previous rounds.
+    /// Only allocations without a token
     pub accumulated_total_amount: String,
-    /// The total amount of tokens to be distributed in a round. Includes amounts only from one round
+    /// The total amount of tokens to be distributed in a round. Includes amounts only from one round.
+    /// Only allocations without a token
     pub round_total_amount: String,
+    /// The same totals for each token given in the allocations
+    pub token_totals: Vec<TokenTotalsResult>,
+    /// Allocations of this round removed because of the denylist
+    pub exclusions: Vec<ExclusionResult>,
+    /// Allocations of this round changed by the caps or the minimum claim
+    pub limits: Vec<LimitResult>,
+    /// Capped amount of this round that wasn't given to anyone
+    pub burned_amount: String,
 }
 
-/// A node in a Merkle tree
-#[derive(Debug, Clone)]
-pub struct Node {
-    pub left_child: Option<Box<Node>>,
-    pub right_child: Option<Box<Node>>,
-    pub accessible_addresses: HashSet<FieldElement>,
-    pub value: FieldElement,
+/// An allocation removed because of the denylist
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExclusionResult {
+    pub address: String,
+    /// The token of the allocation, if it has one
+    pub token: Option<String>,
+    /// The amount the address would have gotten in this round
+    pub amount: String,
+    /// Why the address is denied
+    pub reason: String,
+    /// True if the amount was shared among the other addresses, false if it was dropped
+    pub redistributed: bool,
+}
+
+/// Totals of one token in a round
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TokenTotalsResult {
+    /// The token contract address
+    pub token: String,
+    /// Includes amounts from all previous rounds
+    pub accumulated_total_amount: String,
+    /// Includes amounts only from one round
+    pub round_total_amount: String,
+}
+
+/// An allocation changed by the caps or the minimum claim
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LimitResult {
+    pub address: String,
+    /// The token of the allocation, if it has one
+    pub token: Option<String>,
+    pub kind: LimitKind,
+    /// The amount cut by a cap, or the amount carried forward to the next rounds
+    pub amount: String,
 }
 
 /// Data coming directly from raw JSONs
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct JSONAllocation {
     pub address: String,
     pub amount: String,
+    /// Optional token contract address. Amounts are accumulated separately for each token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Optional values hashed into the leaf after the address and amount, e.g. an unlock timestamp
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_fields: Vec<String>,
 }
 
 /// Accumulated allocation data. Based on JSON data plus
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CumulativeAllocation {
     pub address: FieldElement,
     pub cumulative_amount: u128,
+    /// The token of the allocation. Hashed into the leaf right after the amount
+    pub token: Option<FieldElement>,
+    /// Extra values hashed into the leaf, in order. Empty for plain (address, amount) leaves
+    pub extra_fields: Vec<FieldElement>,
+}
+
+/// Settings of a campaign, read from an optional campaign.json in the input folder
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CampaignConfig {
+    /// How the leaves and nodes of the trees are hashed
+    pub hash_scheme: HashScheme,
+    /// What happens to the amounts of denied addresses
+    pub exclusion_policy: ExclusionPolicy,
+    /// Addresses whose allocations are removed. Read from denylist.json in the input folder
+    #[serde(skip)]
+    pub denylist: Vec<DenylistEntry>,
+    /// The most an address can get in one round
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub round_cap: Option<u128>,
+    /// The most an address can get in all rounds together
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub cumulative_cap: Option<u128>,
+    /// Smaller amounts are carried forward to the next rounds until they reach this
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub min_claim: Option<u128>,
+    /// What happens to the amounts cut by the caps
+    pub cap_policy: CapPolicy,
+    /// The round that goes to the first slot of the contract. Defaults to the smallest round found
+    pub first_round: Option<u8>,
+    /// Roots already added to the contract, in slot order. Read from published_roots.json in the input folder
+    #[serde(skip)]
+    pub published_roots: Vec<FieldElement>,
+    /// The lifecycle states set by the operators. Read from round_states.json in the input folder
+    #[serde(skip)]
+    pub round_states: BTreeMap<u8, RoundState>,
+}
+
+/// Amounts are given as strings like in the input files, as they don't fit in JSON numbers
+fn deserialize_amount<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u128>, D::Error> {
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    value
+        .map(|v| v.parse::<u128>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// An address whose allocations are removed, e.g. a sybil, an exploit contract or a treasury
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DenylistEntry {
+    pub address: String,
+    /// Why the address is denied. Shown in the exclusion records
+    #[serde(default)]
+    pub reason: String,
+    /// The first round the address is denied from. Leave out to deny it in all rounds.
+    /// Amounts of earlier rounds stay in the address's cumulative amount, so already published roots don't change
+    #[serde(default)]
+    pub from_round: Option<u8>,
+    /// The parsed address. Set when the denylist is read
+    #[serde(skip)]
+    pub felt: FieldElement,
+}
+
+/// Record of an allocation removed because of the denylist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExclusionRecord {
+    pub round: u8,
+    pub address: FieldElement,
+    pub token: Option<FieldElement>,
+    /// The amount the address would have gotten in this round
+    pub amount: u128,
+    pub reason: String,
+    /// True if the amount was shared among the other addresses, false if it was dropped
+    pub redistributed: bool,
+}
+
+/// Tokens of an address released over several rounds, read from vesting.json in the input folder
+#[derive(Deserialize, Debug, Clone)]
+pub struct VestingSchedule {
+    pub address: String,
+    /// The amount released over the whole schedule
+    pub total: String,
+    /// Optional token the amounts are paid in, like in the input rows
+    #[serde(default)]
+    pub token: Option<String>,
+    /// The first round of the schedule
+    pub start_round: u8,
+    /// Number of rounds at the start that release nothing. The first round after the cliff releases everything vested so far
+    #[serde(default)]
+    pub cliff: u8,
+    /// Number of rounds the schedule lasts, including the cliff
+    pub duration: u8,
+    /// How the released amount is spread over the rounds
+    #[serde(default)]
+    pub curve: VestingCurve,
+}
+
+/// Amount released to an address in one round of a vesting schedule
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VestingRound {
+    pub round: u8,
+    /// The token of the schedule, if it has one
+    pub token: Option<String>,
+    /// The amount released in this round
+    pub amount: String,
+    /// The amount released by this schedule in this and all previous rounds
+    pub cumulative_amount: String,
+    /// Whether the round has an input file, so the amount is in its allocations
+    pub released: bool,
+}
+
+/// Why the amount of an allocation was changed
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    /// Cut to the cap of one round
+    RoundCap,
+    /// Cut to the cap of all rounds together
+    CumulativeCap,
+    /// Carried forward as the address's pending amount is below the minimum claim
+    BelowMinClaim,
+}
+
+/// Record of an allocation changed by the caps or the minimum claim
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitRecord {
+    pub round: u8,
+    pub address: FieldElement,
+    pub token: Option<FieldElement>,
+    pub kind: LimitKind,
+    /// The amount cut by a cap, or the amount carried forward to the next rounds
+    pub amount: u128,
+}
+
+/// Which roots still have to be added to the contract, and in which order
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PublicationPlan {
+    /// The slot the next add_root call writes to
+    pub next_free_slot: u64,
+    /// Every round that can be published, in slot order
+    pub steps: Vec<PublicationStep>,
+    /// Rounds without data. Later rounds can't be published before these
+    pub missing_rounds: Vec<u8>,
+}
+
+/// The publication state of one round
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PublicationStep {
+    pub round: u8,
+    pub slot: u64,
+    pub root: String,
+    pub status: PublicationStatus,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicationStatus {
+    /// The root is in its slot in the contract
+    Published,
+    /// The root still has to be added with add_root
+    Pending,
+    /// The contract has a different root in the slot
+    Mismatch,
+}
+
+/// Where a round is in its review. Only approved and published rounds are served
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundState {
+    /// Built from a file that hasn't been reviewed yet
+    #[default]
+    Draft,
+    /// Passed the validation of the admin API
+    Validated,
+    /// Reviewed by an operator and served as the latest round
+    Approved,
+    /// The root is in the contract
+    Published,
+    /// Not served anymore
+    Retired,
+}
+
+/// Which rounds a request can get
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Only approved and published rounds
+    Served,
+    /// Also drafts and validated rounds, for operators checking a round
+    Preview,
+}
+
+/// A Claimed event emitted by the Distributor contract
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClaimedEvent {
+    pub claimee: FieldElement,
+    /// The amount claimed in total so far. The contract stores cumulative amounts like the trees
+    pub amount: u128,
+    pub block_number: u64,
+    pub transaction_hash: FieldElement,
+}
+
+/// The Claimed events indexed so far
+#[derive(Debug, Clone, Default)]
+pub struct ClaimIndex {
+    /// The last block whose events are indexed
+    pub last_block: Option<u64>,
+    pub events: Vec<ClaimedEvent>,
+    /// The amount each address has claimed in total
+    pub claimed: HashMap<FieldElement, u128>,
+}
+
+/// The allocated amount of an address together with its claims
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AllocationClaimsResult {
+    /// The accumulated amount allocated up to the round
+    pub amount: String,
+    /// The amount the address has claimed from the contract
+    pub claimed: String,
+    /// The amount the address can claim with the latest root added to the contract.
+    /// Always 0 for allocations in a token, as the Distributor doesn't pay them out
+    pub claimable_now: String,
+}
+
+/// Summary of one round and how much of it is claimed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RoundSummaryResult {
+    pub round: u8,
+    /// The slot of the contract the root goes to. Empty if an earlier round is missing
+    pub slot: Option<u64>,
+    pub root: String,
+    pub state: RoundState,
+    pub accumulated_total_amount: String,
+    pub round_total_amount: String,
+    /// The part of the accumulated total that has been claimed
+    pub claimed_amount: String,
+    /// The part of the accumulated total that hasn't been claimed yet
+    pub unclaimed_amount: String,
 }
 
 /// Information about a raw JSON file
@@ -0,0 +1,176 @@
+use serde::Deserialize;
+use serde_json::from_slice;
+use std::{fs, path::Path, str::FromStr};
+use tracing::{info, instrument};
+
+use super::{
+    address::{format_address, parse_optional_address, Address},
+    errors::{ApiError, ErrorKind},
+    math::mul_div,
+    structs::{JSONAllocation, RoundAmounts, VestingRound, VestingSchedule},
+};
+
+/// How the amount of a vesting schedule is spread over its rounds
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VestingCurve {
+    /// The same amount every round
+    #[default]
+    Linear,
+    /// The vested share grows with the square of the elapsed rounds, so most is released towards the end
+    Quadratic,
+}
+
+impl VestingCurve {
+    /// Relative weight of the round at the given offset from the start of the schedule
+    fn weight(&self, offset: u128) -> u128 {
+        match self {
+            VestingCurve::Linear => 1,
+            VestingCurve::Quadratic => 2 * offset + 1,
+        }
+    }
+
+    /// Sum of the weights of all rounds of a schedule
+    fn total_weight(&self, duration: u128) -> u128 {
+        match self {
+            VestingCurve::Linear => duration,
+            VestingCurve::Quadratic => duration * duration,
+        }
+    }
+}
+
+/// Splits the total of a schedule into the amount of each of its rounds, cliff rounds included.
+/// Amounts are rounded down and the rounding dust is released in the final round
+pub fn expand_schedule(schedule: &VestingSchedule) -> Result<Vec<(u8, u128)>, String> {
+    Address::from_str(&schedule.address)?;
+    parse_optional_address(schedule.token.as_deref())?;
+    let total = schedule
+        .total
+        .parse::<u128>()
+        .map_err(|_| "Invalid vesting total".to_string())?;
+    if schedule.start_round == 0 {
+        return Err("Vesting can't start from round 0".to_string());
+    }
+    if schedule.duration == 0 {
+        return Err("Vesting duration must be at least one round".to_string());
+    }
+    if schedule.cliff >= schedule.duration {
+        return Err("Vesting cliff must be shorter than the duration".to_string());
+    }
+    if schedule.start_round as u16 + schedule.duration as u16 - 1 > u8::MAX as u16 {
+        return Err("Vesting schedule goes past the last possible round".to_string());
+    }
+
+    let duration = schedule.duration as u128;
+    let total_weight = schedule.curve.total_weight(duration);
+
+    let mut amounts: Vec<(u8, u128)> = vec![];
+    let mut released = 0_u128;
+    let mut vested = 0_u128;
+    for offset in 0..duration {
+        let round = schedule.start_round + offset as u8;
+        if offset == duration - 1 {
+            // Whatever is left after rounding down, so that the whole total gets released
+            amounts.push((round, total - released));
+            break;
+        }
+        vested += mul_div(total, schedule.curve.weight(offset), total_weight);
+        if offset < schedule.cliff as u128 {
+            amounts.push((round, 0));
+        } else {
+            amounts.push((round, vested - released));
+            released = vested;
+        }
+    }
+    Ok(amounts)
+}
+
+/// What merging the vesting schedules left out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VestingMerge {
+    /// The index in vesting.json and the error of each invalid schedule. They're skipped
+    pub invalid: Vec<(usize, String)>,
+    /// The schedule index, round and amount of the tranches of rounds without an input file.
+    /// They're added once the file is there
+    pub pending: Vec<(usize, u8, u128)>,
+}
+
+/// Adds the amounts of the vesting schedules to the rounds they're released in.
+/// Only rounds with an input file get them, later tranches are only shown by the preview
+#[instrument(skip_all, fields(schedules = schedules.len()))]
+pub fn merge_vesting_rounds(
+    round_amounts: &mut [RoundAmounts],
+    schedules: &[VestingSchedule],
+) -> VestingMerge {
+    let mut merge = VestingMerge::default();
+    for (index, schedule) in schedules.iter().enumerate() {
+        let tranches = match expand_schedule(schedule) {
+            Ok(v) => v,
+            Err(e) => {
+                merge.invalid.push((index, e));
+                continue;
+            }
+        };
+        for (round, amount) in tranches {
+            if amount == 0 {
+                continue;
+            }
+            match round_amounts.iter_mut().find(|r| r.round == round) {
+                Some(existing) => existing.amounts.push(JSONAllocation {
+                    address: schedule.address.clone(),
+                    amount: amount.to_string(),
+                    token: schedule.token.clone(),
+                    ..Default::default()
+                }),
+                None => merge.pending.push((index, round, amount)),
+            }
+        }
+    }
+    merge
+}
+
+/// Every round of the schedules of an address, in the order the schedules are given.
+/// Only the tranches of the given rounds, which have an input file, are released
+pub fn vesting_preview(
+    schedules: &[VestingSchedule],
+    address: &str,
+    rounds: &[u8],
+) -> Result<Vec<VestingRound>, ApiError> {
+    let address =
+        Address::from_str(address).map_err(|e| ApiError::new(ErrorKind::InvalidAddress, e))?;
+    // An invalid schedule of the address is an error in vesting.json, not in the query
+    let invalid = |e: String| ApiError::new(ErrorKind::Other, e);
+
+    let mut preview: Vec<VestingRound> = vec![];
+    for schedule in schedules.iter() {
+        if Address::from_str(&schedule.address).ok() != Some(address) {
+            continue;
+        }
+        let token = parse_optional_address(schedule.token.as_deref()).map_err(invalid)?;
+        let mut cumulative_amount = 0_u128;
+        for (round, amount) in expand_schedule(schedule).map_err(invalid)? {
+            cumulative_amount += amount;
+            preview.push(VestingRound {
+                round,
+                token: token.as_ref().map(format_address),
+                amount: amount.to_string(),
+                cumulative_amount: cumulative_amount.to_string(),
+                released: rounds.contains(&round),
+            });
+        }
+    }
+    Ok(preview)
+}
+
+/// Reads the vesting schedules from vesting.json in the input folder. Empty if there's no such file
+pub fn read_vesting_schedules(filepath: &str) -> Vec<VestingSchedule> {
+    let path = Path::new(filepath).join("vesting.json");
+    if !path.exists() {
+        return Vec::new();
+    }
+    let contents = fs::read(&path).expect("Failed to read vesting schedules");
+    let schedules: Vec<VestingSchedule> =
+        from_slice(&contents).expect("Failed to deserialize vesting schedules");
+    info!(schedules = schedules.len(), "Read vesting schedules");
+    schedules
+}
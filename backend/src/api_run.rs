@@ -1,25 +1,79 @@
-use actix_web::{middleware, App, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use defispring::api::{
+    admin::{admin_service, AdminConfig, AdminState},
     data_storage::update_api_data,
-    endpoints::{get_allocation_amount, get_calldata, get_root, ApiDoc},
+    endpoints::{
+        get_allocation_amount, get_calldata, get_metrics, get_multiproof_calldata,
+        get_publication_plan, get_root, get_rounds, get_vesting_schedule, ApiDoc,
+    },
+    generator::run_generate,
+    indexer::{start_indexer, IndexerConfig},
+    logging::init_logging,
+    metrics::RequestMetrics,
+    rate_limit::{RateLimitConfig, RateLimiter},
 };
+use std::{env, process};
+use tracing::{error, info};
+use tracing_actix_web::TracingLogger;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    init_logging();
+
+    // `api_run generate ...` writes synthetic input files instead of starting the API
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("generate") {
+        if let Err(e) = run_generate(&args[2..]) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     update_api_data();
 
+    match IndexerConfig::from_env() {
+        Some(config) => {
+            start_indexer(config);
+        }
+        None => info!("STARKNET_RPC_URL or DISTRIBUTOR_ADDRESS not set, claims aren't indexed"),
+    }
+
+    let admin = AdminConfig::from_env().map(|config| web::Data::new(AdminState::new(config)));
+    if admin.is_none() {
+        info!("ADMIN_API_KEYS not set, the admin API is disabled");
+    }
+
     let openapi = ApiDoc::openapi();
+    // Shared by all workers, so the global limits hold for the whole server
+    let rate_limiter = RateLimiter::new(RateLimitConfig::from_env());
 
-    println!("API ready");
+    info!("API ready");
 
     HttpServer::new(move || {
+        let admin = admin.clone();
         App::new()
+            .wrap(middleware::Compress::default())
+            .wrap(rate_limiter.clone())
             .wrap(middleware::DefaultHeaders::new().add(("Access-Control-Allow-Origin", "*")))
+            .wrap(RequestMetrics)
+            .wrap(TracingLogger::default())
             .service(get_calldata)
+            .service(get_multiproof_calldata)
             .service(get_allocation_amount)
             .service(get_root)
+            .service(get_vesting_schedule)
+            .service(get_publication_plan)
+            .service(get_rounds)
+            .service(get_metrics)
+            .configure(|cfg| {
+                // Also used by the public endpoints to authorize previews
+                if let Some(state) = admin {
+                    cfg.app_data(state.clone()).service(admin_service(state));
+                }
+            })
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", openapi.clone()),
             )
@@ -14,21 +14,29 @@ fn normal_address_amount() {
     allocations.push(CumulativeAllocation {
         address: first,
         cumulative_amount: 1,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: second,
         cumulative_amount: 2,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: third,
         cumulative_amount: 3,
+        ..Default::default()
     });
 
     let round_data = RoundTreeData {
         round: 1_u8,
+        slot: Some(0),
         accumulated_total_amount: 10_u128,
         round_total_amount: 6_u128,
         tree: MerkleTree::new(allocations),
+        token_totals: vec![],
+        exclusions: vec![],
+        limits: vec![],
+        burned_amount: 0,
     };
 
     assert!(round_data.address_amount(first).unwrap() == 1_u128);
@@ -0,0 +1,74 @@
+/// This file tests parsing and formatting addresses
+use defispring::api::{
+    address::{format_address, Address},
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use starknet_crypto::FieldElement;
+use std::str::FromStr;
+
+/// Tests that the same address in different formats is the same
+#[test]
+fn equivalent_formats() {
+    let one = Address::from_str("0x1").unwrap();
+    assert_eq!(Address::from_str("0x01").unwrap(), one);
+    assert_eq!(Address::from_str("1").unwrap(), one);
+    assert_eq!(Address::from_str(" 0x1 ").unwrap(), one);
+    assert_eq!(one.felt(), FieldElement::ONE);
+}
+
+/// Tests that values that aren't contract addresses are rejected
+#[test]
+fn invalid_addresses() {
+    assert_eq!(Address::from_str("").unwrap_err(), "Address is empty");
+    assert_eq!(Address::from_str("   ").unwrap_err(), "Address is empty");
+    assert!(Address::from_str("0xg").is_err());
+    assert!(Address::from_str("-1").is_err());
+
+    // The largest address is 2^251 - 1
+    let max = "0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+    assert!(Address::from_str(max).is_ok());
+    let bound = "0x800000000000000000000000000000000000000000000000000000000000000";
+    assert_eq!(
+        Address::from_str(bound).unwrap_err(),
+        "Address is out of range"
+    );
+    // Valid felts above the bound are rejected too
+    assert!(Address::try_from(FieldElement::MAX).is_err());
+}
+
+/// Tests that addresses are rendered with 64 hex digits
+#[test]
+fn canonical_format() {
+    assert_eq!(
+        Address::from_str("255").unwrap().to_string(),
+        "0x00000000000000000000000000000000000000000000000000000000000000ff"
+    );
+    let max = "0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+    assert_eq!(
+        format_address(&FieldElement::from_str(max).unwrap()),
+        "0x07ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+    );
+}
+
+/// Tests that lookups accept any format of the address and reject invalid ones
+#[test]
+fn lookups() {
+    let allocations: Vec<CumulativeAllocation> = (1..=3_u64)
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i),
+            cumulative_amount: i as u128,
+            ..Default::default()
+        })
+        .collect();
+    let mt = MerkleTree::new(allocations);
+
+    let calldata = mt.address_calldata("0x0002").unwrap();
+    assert_eq!(calldata.amount, "0x2");
+    assert_eq!(mt.address_calldata("2").unwrap().proof, calldata.proof);
+
+    assert_eq!(
+        mt.address_calldata("").unwrap_err().message,
+        "Address is empty"
+    );
+    assert!(mt.multiproof_calldata(&["".to_string()]).is_err());
+}
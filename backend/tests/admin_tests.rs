@@ -0,0 +1,350 @@
+/// This file tests the admin API: authentication, staging, validation, promotion, round states and the audit log
+use actix_web::{
+    http::{header, StatusCode},
+    test as actix_test, web, App,
+};
+use defispring::api::{
+    admin::{
+        admin_service, parse_api_keys, read_audit_log, validate_round, AdminConfig, AdminState,
+        AuditLog, AuditOutcome, RoundStateResult, ValidationReport,
+    },
+    data_storage::{get_api_data, set_round_data, set_round_states},
+    endpoints::get_root,
+    errors::{ApiError, ErrorKind},
+    generator::{generate_allocations, write_round_file},
+    lifecycle::{read_round_states, round_state},
+    merkle_tree::felt_to_b16,
+    processor::{get_raw_root, read_allocations, read_campaign_config},
+    structs::{JSONAllocation, RoundState, Visibility},
+};
+use std::{env, fs, path::PathBuf};
+
+fn folder(name: &str) -> PathBuf {
+    let folder = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+fn row(address: &str, amount: &str) -> JSONAllocation {
+    JSONAllocation {
+        address: address.to_string(),
+        amount: amount.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Tests the names given to the keys and that empty keys are ignored
+#[test]
+fn api_keys() {
+    assert_eq!(
+        parse_api_keys("ops:secret1, secret2,deploy:,"),
+        vec![
+            ("ops".to_string(), "secret1".to_string()),
+            ("key2".to_string(), "secret2".to_string()),
+        ]
+    );
+    assert!(parse_api_keys("").is_empty());
+}
+
+/// Tests that requests without a key can't fill the audit log
+#[test]
+fn denied_requests_aggregated() {
+    let path = env::temp_dir().join("defispring_denied_audit.log");
+    let _ = fs::remove_file(&path);
+    let path = path.to_str().unwrap();
+    let audit = AuditLog::open(path);
+    for _ in 0..1000 {
+        audit.record("unknown", "authenticate", None, AuditOutcome::Denied, None);
+    }
+    audit.record("unknown", "preview", None, AuditOutcome::Denied, None);
+    audit.record("ops", "upload", Some(1), AuditOutcome::Ok, None);
+
+    let actions: Vec<String> = read_audit_log(path).into_iter().map(|e| e.action).collect();
+    assert_eq!(actions, vec!["authenticate", "preview", "upload"]);
+    fs::remove_file(path).unwrap();
+}
+
+/// Tests that keys only match exactly, and that a corrupt round file is a bad request
+#[actix_web::test]
+async fn keys_and_corrupt_files() {
+    let input = folder("defispring_admin_corrupt");
+    fs::create_dir_all(input.join("staging")).unwrap();
+    fs::write(input.join("staging/raw_3.zip"), "not a zip").unwrap();
+    let audit_log = input.join("audit.log").to_str().unwrap().to_string();
+    let state = web::Data::new(AdminState::new(AdminConfig {
+        keys: parse_api_keys("ops:secret,deploy:secret2"),
+        input_folder: input.to_str().unwrap().to_string(),
+        audit_log: audit_log.clone(),
+        max_upload_bytes: 1024,
+    }));
+    let app = actix_test::init_service(App::new().service(admin_service(state))).await;
+
+    for (key, status) in [
+        ("secre", StatusCode::UNAUTHORIZED),
+        ("secret22", StatusCode::UNAUTHORIZED),
+        ("secret2", StatusCode::BAD_REQUEST),
+    ] {
+        let req = actix_test::TestRequest::post()
+            .uri("/admin/rounds/3/validate")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", key)));
+        let res = actix_test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), status);
+    }
+    let entries = read_audit_log(&audit_log);
+    let last = entries.last().unwrap();
+    assert_eq!(
+        (last.actor.as_str(), last.action.as_str()),
+        ("deploy", "validate")
+    );
+    fs::remove_dir_all(input).unwrap();
+}
+
+/// Tests the reports of invalid rows, valid rounds and rounds that can't be served
+#[test]
+fn validation_reports() {
+    let input = folder("defispring_admin_validation");
+    write_round_file(&input, 1, &generate_allocations(3)).unwrap();
+    let input = input.to_str().unwrap();
+
+    let (report, rounds) = validate_round(
+        input,
+        2,
+        vec![
+            row("0x1", "10"),
+            row("not_an_address", "10"),
+            row("0x2", "-5"),
+        ],
+    );
+    assert!(!report.valid && rounds.is_none());
+    assert_eq!(report.invalid_rows, 2);
+    assert_eq!(
+        report.errors.iter().map(|e| e.row).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert!(report.build.is_none());
+
+    // Repeated addresses are added up
+    let (report, rounds) = validate_round(input, 2, vec![row("0x1", "10"), row("0x01", "5")]);
+    assert!(report.valid);
+    assert_eq!(report.repeated_addresses, 1);
+    let build = report.build.unwrap();
+    assert_eq!(build.slot, Some(1));
+    assert_eq!(build.round_total_amount, "15");
+    // 1010 + 1020 + 1030 from round 1
+    assert_eq!(build.accumulated_total_amount, "3075");
+    assert_eq!(rounds.unwrap().len(), 2);
+
+    let (report, _) = validate_round(input, 4, vec![row("0x1", "10")]);
+    assert_eq!(
+        report.build_errors,
+        vec!["Round 4 has missing predecessors and wouldn't be served"]
+    );
+    let (report, _) = validate_round(input, 2, vec![]);
+    assert_eq!(report.build_errors, vec!["The file has no rows"]);
+
+    // Replacing round 1 would change its published root
+    fs::write(
+        PathBuf::from(input).join("published_roots.json"),
+        format!("[\"{}\"]", get_raw_root_of(input)),
+    )
+    .unwrap();
+    let (report, rounds) = validate_round(input, 1, vec![row("0x1", "10")]);
+    assert!(!report.valid && rounds.is_none());
+    assert_eq!(
+        report.build_errors,
+        vec!["Round 1 would change the root already published in slot 0"]
+    );
+    let (report, _) = validate_round(input, 2, vec![row("0x1", "10")]);
+    assert!(report.valid);
+
+    fs::remove_dir_all(input).unwrap();
+}
+
+fn get_raw_root_of(input: &str) -> String {
+    felt_to_b16(&read_allocations(input.to_string())[0].tree.root)
+}
+
+/// Tests the whole flow through the endpoints, from the upload to the retirement of a round.
+/// A single test, as the round data is global
+#[actix_web::test]
+async fn admin_workflow() {
+    let input = folder("defispring_admin_workflow");
+    write_round_file(&input, 1, &generate_allocations(3)).unwrap();
+    fs::write(input.join("round_states.json"), r#"{ "1": "approved" }"#).unwrap();
+    let uploads = folder("defispring_admin_uploads");
+    write_round_file(&uploads, 2, &[row("0x1", "10"), row("0x4", "20")]).unwrap();
+    let round_file = fs::read(uploads.join("raw_2.zip")).unwrap();
+
+    let input_folder = input.to_str().unwrap().to_string();
+    let audit_log = input.join("audit.log").to_str().unwrap().to_string();
+    set_round_states(read_campaign_config(&input_folder).round_states);
+    set_round_data(read_allocations(input_folder.clone()));
+    let state = web::Data::new(AdminState::new(AdminConfig {
+        keys: parse_api_keys("ops:secret"),
+        input_folder: input_folder.clone(),
+        audit_log: audit_log.clone(),
+        max_upload_bytes: 1024 * 1024,
+    }));
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .service(get_root)
+            .service(admin_service(state)),
+    )
+    .await;
+
+    let request = |method: &str, path: &str, key: &str| {
+        let req = match method {
+            "GET" => actix_test::TestRequest::get(),
+            _ => actix_test::TestRequest::post(),
+        };
+        req.uri(path)
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", key)))
+    };
+    let set_state = |round: u8, state: &str| {
+        request("POST", &format!("/admin/rounds/{}/state", round), "secret")
+            .set_json(serde_json::json!({ "state": state }))
+    };
+    macro_rules! call {
+        ($req:expr) => {
+            actix_test::call_service(&app, $req.to_request()).await
+        };
+    }
+    let states = || -> Vec<(u8, RoundState)> {
+        let data = get_api_data();
+        data.rounds
+            .iter()
+            .map(|r| (r.round, round_state(r, &data.config)))
+            .collect()
+    };
+
+    // Requests without a valid key are rejected and nothing is staged
+    let res = call!(actix_test::TestRequest::post().uri("/admin/rounds/2/upload"));
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res =
+        call!(request("POST", "/admin/rounds/2/upload", "wrong").set_payload(round_file.clone()));
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(!input.join("staging").exists());
+
+    let res = call!(request("POST", "/admin/rounds/2/upload", "secret").set_payload("not a zip"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = call!(request("POST", "/admin/rounds/2/promote", "secret"));
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = call!(request("POST", "/admin/rounds/2/upload", "secret").set_payload(round_file));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(input.join("staging/raw_2.zip").exists());
+    let res = call!(request("GET", "/admin/rounds/2/report", "secret"));
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = call!(request("POST", "/admin/rounds/2/validate", "secret"));
+    assert_eq!(res.status(), StatusCode::OK);
+    let report: ValidationReport = actix_test::read_body_json(res).await;
+    assert!(report.valid);
+    let res = call!(request("GET", "/admin/rounds/2/report", "secret"));
+    assert_eq!(
+        actix_test::read_body_json::<ValidationReport, _>(res).await,
+        report
+    );
+    // Validating doesn't change the served rounds
+    assert_eq!(get_raw_root(None, Visibility::Served).unwrap().slot, 0);
+
+    // A promoted round is only served in previews until it's approved
+    let res = call!(request("POST", "/admin/rounds/2/promote", "secret"));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(input.join("raw_2.zip").exists());
+    assert!(!input.join("staging/raw_2.zip").exists());
+    assert_eq!(
+        states(),
+        vec![(1, RoundState::Approved), (2, RoundState::Validated)]
+    );
+    assert_eq!(get_raw_root(None, Visibility::Served).unwrap().slot, 0);
+    assert_eq!(
+        get_raw_root(Some(2), Visibility::Served)
+            .unwrap_err()
+            .message,
+        "Round hasn't been approved"
+    );
+    let preview = get_raw_root(None, Visibility::Preview).unwrap();
+    assert_eq!(preview.slot, 1);
+    assert_eq!(preview.root, report.build.unwrap().root);
+
+    // Previews through the public endpoints need a key
+    let res = call!(actix_test::TestRequest::get().uri("/get_root?preview=true"));
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = call!(request("GET", "/get_root?preview=true", "secret"));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(header::ETAG).is_none());
+
+    let res = call!(set_state(2, "published"));
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let res = call!(set_state(2, "approved"));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(get_raw_root(None, Visibility::Served).unwrap().slot, 1);
+    let res = call!(request("GET", "/admin/rounds", "secret"));
+    let rounds: Vec<RoundStateResult> = actix_test::read_body_json(res).await;
+    assert_eq!(rounds[1].state, RoundState::Approved);
+    let res = call!(set_state(2, "published"));
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // Retired rounds aren't served, not even in previews, also after a restart
+    let res = call!(set_state(2, "retired"));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(get_raw_root(None, Visibility::Preview).unwrap().slot, 0);
+    assert_eq!(
+        get_raw_root(Some(2), Visibility::Preview).unwrap_err(),
+        ApiError::new(ErrorKind::RoundNotServed, "Round has been retired")
+    );
+    assert_eq!(
+        read_round_states(&input_folder).get(&2),
+        Some(&RoundState::Retired)
+    );
+
+    // A file added to the folder is a draft. Validating it validates the round
+    write_round_file(&input, 3, &[row("0x5", "30")]).unwrap();
+    set_round_data(read_allocations(input_folder.clone()));
+    assert_eq!(states()[2], (3, RoundState::Draft));
+    let res = call!(request("POST", "/admin/rounds/3/validate", "secret"));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(states()[2], (3, RoundState::Validated));
+    let res = call!(request("POST", "/admin/rounds/3/promote", "secret"));
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let entries = read_audit_log(&audit_log);
+    let summary: Vec<(&str, &str, AuditOutcome)> = entries
+        .iter()
+        .map(|e| (e.actor.as_str(), e.action.as_str(), e.outcome))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            // The second request without a key is only counted
+            ("unknown", "authenticate", AuditOutcome::Denied),
+            ("ops", "upload", AuditOutcome::Failed),
+            ("ops", "promote", AuditOutcome::Failed),
+            ("ops", "upload", AuditOutcome::Ok),
+            ("ops", "report", AuditOutcome::Failed),
+            ("ops", "validate", AuditOutcome::Ok),
+            ("ops", "report", AuditOutcome::Ok),
+            ("ops", "promote", AuditOutcome::Ok),
+            ("unknown", "preview", AuditOutcome::Denied),
+            ("ops", "preview", AuditOutcome::Ok),
+            ("ops", "set_state", AuditOutcome::Failed),
+            ("ops", "set_state", AuditOutcome::Ok),
+            ("ops", "list_rounds", AuditOutcome::Ok),
+            ("ops", "set_state", AuditOutcome::Failed),
+            ("ops", "set_state", AuditOutcome::Ok),
+            ("ops", "validate", AuditOutcome::Ok),
+            ("ops", "promote", AuditOutcome::Failed),
+        ]
+    );
+    assert_eq!(entries[11].detail.as_deref(), Some("validated -> approved"));
+    assert!(entries
+        .iter()
+        .all(|e| !format!("{:?}", e).contains("secret")));
+
+    set_round_data(vec![]);
+    fs::remove_dir_all(&input).unwrap();
+    fs::remove_dir_all(&uploads).unwrap();
+}
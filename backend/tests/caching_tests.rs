@@ -0,0 +1,138 @@
+/// This file tests the cache headers and the conditional requests of the endpoints
+use actix_web::{
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    middleware, test, App,
+};
+use defispring::api::{
+    caching::{LATEST_ROUND_MAX_AGE, PAST_ROUND_MAX_AGE},
+    data_storage::{set_round_data, set_round_states},
+    endpoints::{get_allocation_amount, get_calldata, get_root},
+    structs::{CampaignConfig, RoundState, RoundTreeData},
+};
+use std::collections::BTreeMap;
+
+mod common;
+use common::rounds;
+
+/// Two rounds with the same addresses. The amounts of the second round are given
+fn two_rounds(amount: &str) -> Vec<RoundTreeData> {
+    rounds(
+        &[
+            &[("0x1", "10"), ("0x2", "10")],
+            &[("0x1", amount), ("0x2", amount)],
+        ],
+        &CampaignConfig::default(),
+    )
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers.get(name).map(|v| v.to_str().unwrap().to_string())
+}
+
+/// Tests the ETags and max ages, that a matching If-None-Match gives 304 until the data changes and the compression.
+/// A single test, as the round data is global
+#[actix_web::test]
+async fn conditional_requests() {
+    set_round_states(BTreeMap::from([
+        (1, RoundState::Approved),
+        (2, RoundState::Approved),
+    ]));
+    set_round_data(two_rounds("20"));
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware::Compress::default())
+            .service(get_calldata)
+            .service(get_allocation_amount)
+            .service(get_root),
+    )
+    .await;
+    let get = |uri: &str, etag: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(etag) = etag {
+            req = req.insert_header((header::IF_NONE_MATCH, HeaderValue::from_str(etag).unwrap()));
+        }
+        req.to_request()
+    };
+
+    // A past round can be cached for long, the latest round only briefly
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x1", None)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = header_value(res.headers(), header::ETAG).unwrap();
+    assert_eq!(
+        header_value(res.headers(), header::CACHE_CONTROL).unwrap(),
+        format!("public, max-age={}", PAST_ROUND_MAX_AGE)
+    );
+    let res = test::call_service(&app, get("/get_calldata?address=0x1", None)).await;
+    let latest_etag = header_value(res.headers(), header::ETAG).unwrap();
+    assert_ne!(latest_etag, etag);
+    assert_eq!(
+        header_value(res.headers(), header::CACHE_CONTROL).unwrap(),
+        format!("public, max-age={}", LATEST_ROUND_MAX_AGE)
+    );
+
+    // The same address written differently has the same ETag, other addresses and endpoints don't
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x01", None)).await;
+    assert_eq!(header_value(res.headers(), header::ETAG).unwrap(), etag);
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x2", None)).await;
+    assert_ne!(header_value(res.headers(), header::ETAG).unwrap(), etag);
+    let res = test::call_service(
+        &app,
+        get("/get_allocation_amount?round=1&address=0x1", None),
+    )
+    .await;
+    assert_ne!(header_value(res.headers(), header::ETAG).unwrap(), etag);
+
+    // The client already has the response
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x1", Some(&etag))).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(header_value(res.headers(), header::ETAG).unwrap(), etag);
+    assert!(test::read_body(res).await.is_empty());
+    let res = test::call_service(
+        &app,
+        get("/get_calldata?round=1&address=0x1", Some("\"other\"")),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Errors and the claims aren't cached
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x5", None)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert!(header_value(res.headers(), header::ETAG).is_none());
+    let res = test::call_service(
+        &app,
+        get(
+            "/get_allocation_amount?address=0x1&include_claims=true",
+            None,
+        ),
+    )
+    .await;
+    assert!(header_value(res.headers(), header::CACHE_CONTROL).is_none());
+
+    let res = test::call_service(&app, get("/get_root?round=2", None)).await;
+    let root_etag = header_value(res.headers(), header::ETAG).unwrap();
+
+    // The latest round changes, the past round doesn't
+    set_round_data(two_rounds("30"));
+    let res = test::call_service(&app, get("/get_calldata?address=0x1", Some(&latest_etag))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = test::call_service(&app, get("/get_root?round=2", Some(&root_etag))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = test::call_service(&app, get("/get_calldata?round=1&address=0x1", Some(&etag))).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    // Responses are compressed when the client accepts it
+    let req = test::TestRequest::get()
+        .uri("/get_calldata?round=1&address=0x1")
+        .insert_header((header::ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        header_value(res.headers(), header::CONTENT_ENCODING).unwrap(),
+        "gzip"
+    );
+    assert_eq!(header_value(res.headers(), header::ETAG).unwrap(), etag);
+}
@@ -0,0 +1,190 @@
+/// This file tests indexing Claimed events and the claimed amounts of the rounds
+use defispring::api::{
+    indexer::{
+        claimed_in_round, read_claims, sync_claims, unclaimed_in_round, write_claims, EventSource,
+        EventsPage, FixtureEventSource, RpcEventSource, CLAIMED_SELECTOR,
+    },
+    sequencing::claimable_round,
+    structs::{CampaignConfig, ClaimIndex, JSONAllocation},
+};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+mod common;
+use common::{allocation, felt, rounds, rounds_of};
+
+const FIXTURE: &str = "./tests/test_claims_input/events.json";
+
+fn synced_index() -> ClaimIndex {
+    let mut index = ClaimIndex::default();
+    sync_claims(
+        &FixtureEventSource::read(FIXTURE),
+        &felt("0x123"),
+        &mut index,
+    )
+    .unwrap();
+    index
+}
+
+/// Tests that the claims of the contract are indexed and later claims replace earlier ones
+#[test]
+fn sync_from_fixture() {
+    let source = FixtureEventSource::read(FIXTURE);
+    let mut index = ClaimIndex::default();
+
+    // Events of other contracts, other kinds and pending blocks are skipped
+    assert_eq!(sync_claims(&source, &felt("0x123"), &mut index).unwrap(), 3);
+    assert_eq!(index.claimed(&felt("0x1")), 150);
+    assert_eq!(index.claimed(&felt("0x2")), 50);
+    assert_eq!(index.claimed(&felt("0x3")), 0);
+    assert_eq!(index.last_block, Some(12));
+
+    // Syncing again doesn't add the same events twice
+    assert_eq!(sync_claims(&source, &felt("0x123"), &mut index).unwrap(), 0);
+    assert_eq!(index.events.len(), 3);
+}
+
+/// Tests that the indexed events are stored and read back
+#[test]
+fn persisted_claims() {
+    let index = synced_index();
+    let path = std::env::temp_dir().join("defispring_claims_test.json");
+    let path = path.to_str().unwrap();
+
+    write_claims(path, &index).unwrap();
+    let read = read_claims(path);
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(read.events, index.events);
+    assert_eq!(read.last_block, Some(12));
+    assert_eq!(read.claimed(&felt("0x1")), 150);
+
+    assert!(read_claims("./tests/test_claims_input/missing.json")
+        .events
+        .is_empty());
+}
+
+/// Tests the claimed and unclaimed amounts of the rounds and what can be claimed now
+#[test]
+fn claimed_amounts_of_rounds() {
+    let rounds = rounds(
+        &[
+            &[("0x1", "100"), ("0x2", "40"), ("0x3", "10")],
+            &[("0x1", "100"), ("0x2", "20")],
+        ],
+        &CampaignConfig::default(),
+    );
+    let index = synced_index();
+
+    // Claims count up to the cumulative amount of the round
+    assert_eq!(claimed_in_round(&rounds[0], &index), 140);
+    assert_eq!(claimed_in_round(&rounds[1], &index), 200);
+    assert_eq!(rounds[1].accumulated_total_amount, 270);
+    assert_eq!(unclaimed_in_round(&rounds[1], &index), 70);
+
+    // Only the first root is in the contract
+    let config = CampaignConfig {
+        published_roots: vec![rounds[0].tree.root],
+        ..Default::default()
+    };
+    assert_eq!(claimable_round(&rounds, &config).unwrap().round, 1);
+
+    // Without the published roots the latest round is used
+    let latest = claimable_round(&rounds, &CampaignConfig::default()).unwrap();
+    assert_eq!(latest.round, 2);
+    assert_eq!(
+        latest.address_amount(felt("0x1")).unwrap() - index.claimed(&felt("0x1")),
+        50
+    );
+}
+
+/// Tests that the unclaimed amount of a round leaves out the allocations paid in other tokens
+#[test]
+fn unclaimed_amounts_with_tokens() {
+    let strk = |address: &str, amount: &str| JSONAllocation {
+        token: Some("0x4718".to_string()),
+        ..allocation(address, amount)
+    };
+    let rounds = rounds_of(
+        vec![vec![
+            allocation("0x1", "200"),
+            strk("0x1", "500"),
+            allocation("0x2", "30"),
+            strk("0x3", "80"),
+        ]],
+        &CampaignConfig::default(),
+    );
+    let index = synced_index();
+
+    // The allocations in a token aren't in the accumulated total
+    assert_eq!(rounds[0].accumulated_total_amount, 230);
+    assert_eq!(claimed_in_round(&rounds[0], &index), 180);
+    assert_eq!(unclaimed_in_round(&rounds[0], &index), 50);
+}
+
+/// Tests the JSON-RPC requests against a local stand-in for a node serving the recorded pages
+#[test]
+fn rpc_source() {
+    let pages: Vec<EventsPage> = serde_json::from_slice(&fs::read(FIXTURE).unwrap()).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let mut requests: Vec<Value> = vec![];
+        for page in pages {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            requests.push(serde_json::from_slice(&body).unwrap());
+
+            let response = json!({ "jsonrpc": "2.0", "id": 1, "result": page }).to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+        requests
+    });
+
+    let source = RpcEventSource::new(&url);
+    let mut index = ClaimIndex::default();
+    // The stand-in doesn't filter by contract, but the event of the other contract is still skipped
+    assert_eq!(sync_claims(&source, &felt("0x123"), &mut index).unwrap(), 3);
+    assert_eq!(index.claimed(&felt("0x1")), 150);
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["method"], "starknet_getEvents");
+    let filter = &requests[0]["params"]["filter"];
+    assert_eq!(filter["keys"][0][0], CLAIMED_SELECTOR);
+    assert_eq!(filter["from_block"]["block_number"], 0);
+    assert!(filter.get("continuation_token").is_none());
+    assert_eq!(
+        requests[1]["params"]["filter"]["continuation_token"],
+        "11-3"
+    );
+
+    // Unreachable nodes are reported as errors
+    let source = RpcEventSource::new("http://127.0.0.1:1");
+    assert!(source.get_events(&felt("0x123"), 0, None).is_err());
+}
@@ -0,0 +1,65 @@
+//! Fixtures shared by the integration tests
+
+// Not every test file uses all of them
+#![allow(dead_code)]
+
+use defispring::api::{
+    processor::transform_allocations_to_cumulative_rounds,
+    structs::{CampaignConfig, JSONAllocation, RoundAmounts, RoundTreeData},
+};
+use starknet_crypto::FieldElement;
+use std::str::FromStr;
+
+pub fn felt(value: &str) -> FieldElement {
+    FieldElement::from_str(value).unwrap()
+}
+
+pub fn allocation(address: &str, amount: &str) -> JSONAllocation {
+    JSONAllocation {
+        address: address.to_string(),
+        amount: amount.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Builds rounds 1, 2, ... from the allocations of each round
+pub fn rounds_of(
+    allocations: Vec<Vec<JSONAllocation>>,
+    config: &CampaignConfig,
+) -> Vec<RoundTreeData> {
+    let round_amounts = allocations
+        .into_iter()
+        .enumerate()
+        .map(|(i, amounts)| RoundAmounts {
+            round: i as u8 + 1,
+            amounts,
+        })
+        .collect();
+    transform_allocations_to_cumulative_rounds(round_amounts, config)
+}
+
+/// Builds rounds 1, 2, ... from the addresses and amounts of each round
+pub fn rounds(rows: &[&[(&str, &str)]], config: &CampaignConfig) -> Vec<RoundTreeData> {
+    let allocations = rows
+        .iter()
+        .map(|round| {
+            round
+                .iter()
+                .map(|(address, amount)| allocation(address, amount))
+                .collect()
+        })
+        .collect();
+    rounds_of(allocations, config)
+}
+
+/// Builds the given rounds, in each of which 0x1 gets 5
+pub fn numbered_rounds(round_numbers: &[u8], config: &CampaignConfig) -> Vec<RoundTreeData> {
+    let round_amounts = round_numbers
+        .iter()
+        .map(|round| RoundAmounts {
+            round: *round,
+            amounts: vec![allocation("0x1", "5")],
+        })
+        .collect();
+    transform_allocations_to_cumulative_rounds(round_amounts, config)
+}
@@ -1,6 +1,6 @@
 use defispring::api::{
     processor::transform_allocations_to_cumulative_rounds,
-    structs::{JSONAllocation, RoundAmounts},
+    structs::{CampaignConfig, JSONAllocation, RoundAmounts},
 };
 use starknet_crypto::FieldElement;
 use std::str::FromStr;
@@ -11,13 +11,10 @@ fn test_odd_data() {
     let two: FieldElement = FieldElement::from_str("0x2").unwrap();
 
     let mut drop: Vec<JSONAllocation> = vec![];
-    drop.push(JSONAllocation {
-        address: "".to_string(),
-        amount: "0".to_string(),
-    });
     drop.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "0".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -25,12 +22,42 @@ fn test_odd_data() {
         round: 1u8,
         amounts: drop,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].address_amount(one).unwrap() == 0_u128);
     assert!(res[0].address_amount(two).unwrap() == 0_u128);
 }
 
+/// Rows with empty addresses or addresses outside the contract address range are skipped
+#[test]
+fn test_invalid_addresses() {
+    for address in [
+        "",
+        "  ",
+        "0x800000000000000000000000000000000000000000000000000000000000000",
+    ] {
+        let round_data = vec![RoundAmounts {
+            round: 1u8,
+            amounts: vec![
+                JSONAllocation {
+                    address: address.to_string(),
+                    amount: "1".to_string(),
+                    ..Default::default()
+                },
+                JSONAllocation {
+                    address: "0x1".to_string(),
+                    amount: "2".to_string(),
+                    ..Default::default()
+                },
+            ],
+        }];
+        let res =
+            transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
+        assert_eq!(res[0].tree.allocations.len(), 1);
+        assert_eq!(res[0].round_total_amount, 2);
+    }
+}
+
 #[test]
 fn test_empty_data() {
     let drop: Vec<JSONAllocation> = vec![];
@@ -40,7 +67,7 @@ fn test_empty_data() {
         round: 1u8,
         amounts: drop,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 0);
 }
@@ -55,14 +82,17 @@ fn test_cumulative_one_round() {
     drop.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -70,7 +100,7 @@ fn test_cumulative_one_round() {
         round: 1u8,
         amounts: drop,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].address_amount(one).unwrap() == 5_u128);
     assert!(res[0].address_amount(two).unwrap() == 6_u128);
@@ -88,18 +118,22 @@ fn test_cumulative_two_rounds() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop2.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "23".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -111,7 +145,7 @@ fn test_cumulative_two_rounds() {
         round: 2u8,
         amounts: drop2,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].address_amount(one).unwrap() == 5_u128);
     assert!(res[0].address_amount(two).unwrap() == 6_u128);
@@ -134,22 +168,27 @@ fn test_cumulative_three_rounds() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop2.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "23".to_string(),
+        ..Default::default()
     });
     drop3.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "33".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -165,7 +204,7 @@ fn test_cumulative_three_rounds() {
         round: 3u8,
         amounts: drop3,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].address_amount(one).unwrap() == 5_u128);
     assert!(res[0].address_amount(two).unwrap() == 6_u128);
@@ -191,18 +230,22 @@ fn test_skip_round() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop3.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "33".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -214,7 +257,7 @@ fn test_skip_round() {
         round: 3u8,
         amounts: drop3,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].round == 1u8);
     assert!(res[1].round == 3u8);
@@ -238,14 +281,17 @@ fn test_big_amounts() {
     drop.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: (u128::MAX / 2).to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: (u128::MAX / 2 - 5).to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "3".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -253,7 +299,7 @@ fn test_big_amounts() {
         round: 1u8,
         amounts: drop,
     });
-    let res = transform_allocations_to_cumulative_rounds(round_data);
+    let res = transform_allocations_to_cumulative_rounds(round_data, &CampaignConfig::default());
 
     assert!(res[0].address_amount(one).unwrap() == u128::MAX / 2);
     assert!(res[0].address_amount(two).unwrap() == u128::MAX / 2 - 2);
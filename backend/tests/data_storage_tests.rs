@@ -0,0 +1,72 @@
+/// This file tests swapping the round data served by the API
+use defispring::api::{
+    data_storage::{get_all_data, get_api_data, set_round_data, set_round_states},
+    merkle_tree::felt_to_b16,
+    processor::{get_raw_allocation_amount, get_raw_allocation_claims, get_raw_root},
+    structs::{CampaignConfig, JSONAllocation, RoundState, Visibility},
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+mod common;
+use common::{allocation, rounds, rounds_of};
+
+/// Tests that handles and snapshots taken before a reload keep the old data while new requests get the new data.
+/// A single test, as the data is global
+#[test]
+fn swap_round_data() {
+    set_round_states(BTreeMap::from([(1, RoundState::Approved)]));
+    set_round_data(rounds(&[&[("0x1", "10")]], &CampaignConfig::default()));
+    let before = get_all_data();
+    let round = before[0].clone();
+    assert_eq!(
+        get_raw_allocation_amount(None, Visibility::Served, "0x1", None),
+        Ok(10)
+    );
+
+    set_round_data(rounds(&[&[("0x1", "20")]], &CampaignConfig::default()));
+
+    // The old handles are unchanged and share the data instead of copying it
+    assert_eq!(round.address_amount(1_u64.into()), Ok(10));
+    assert!(Arc::ptr_eq(&round, &before[0]));
+    assert_eq!(Arc::strong_count(&round), 2);
+
+    let after = get_all_data();
+    assert_eq!(after[0].address_amount(1_u64.into()), Ok(20));
+    assert_eq!(
+        get_raw_allocation_amount(Some(1), Visibility::Served, "0x1", None),
+        Ok(20)
+    );
+    assert_eq!(
+        get_raw_root(None, Visibility::Served).unwrap().root,
+        felt_to_b16(&after[0].tree.root)
+    );
+
+    // A snapshot keeps the states and rounds of the same moment
+    let snapshot = get_api_data();
+    set_round_states(BTreeMap::new());
+    assert_eq!(snapshot.config.round_states[&1], RoundState::Approved);
+    assert!(Arc::ptr_eq(&snapshot.rounds, &get_all_data()));
+    assert!(get_raw_root(None, Visibility::Served).is_err());
+
+    // Allocations in a token are never claimable from the Distributor
+    set_round_states(BTreeMap::from([(1, RoundState::Approved)]));
+    let strk = JSONAllocation {
+        token: Some("0x4718".to_string()),
+        ..allocation("0x1", "30")
+    };
+    set_round_data(rounds_of(
+        vec![vec![allocation("0x1", "10"), strk]],
+        &CampaignConfig::default(),
+    ));
+    let claims = get_raw_allocation_claims(None, Visibility::Served, "0x1", None).unwrap();
+    assert_eq!(claims.claimable_now, "10");
+    let claims =
+        get_raw_allocation_claims(None, Visibility::Served, "0x1", Some("0x4718")).unwrap();
+    assert_eq!(
+        (claims.amount.as_str(), claims.claimable_now.as_str()),
+        ("30", "0")
+    );
+
+    set_round_data(vec![]);
+    assert!(get_api_data().rounds.is_empty());
+}
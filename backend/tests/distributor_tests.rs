@@ -0,0 +1,149 @@
+/// This file replays rounds and claims against a model of the Distributor contract
+use defispring::api::{
+    distributor::Distributor,
+    structs::{CampaignConfig, RoundTreeData},
+};
+use starknet_crypto::FieldElement;
+
+mod common;
+use common::{felt, rounds};
+
+const OWNER: &str = "0x999";
+
+/// Three rounds: 0x1 gets 10 per round, 0x2 gets 20 in rounds 1 and 3, 0x3 joins in round 2
+fn three_rounds() -> Vec<RoundTreeData> {
+    rounds(
+        &[
+            &[("0x1", "10"), ("0x2", "20")],
+            &[("0x1", "10"), ("0x3", "5")],
+            &[("0x1", "10"), ("0x2", "20"), ("0x3", "5")],
+        ],
+        &CampaignConfig::default(),
+    )
+}
+
+fn claim(
+    distributor: &mut Distributor,
+    round: &RoundTreeData,
+    address: &str,
+) -> Result<u128, String> {
+    let calldata = round.tree.address_calldata(address).unwrap();
+    distributor.claim_with_calldata(felt(address), &calldata)
+}
+
+fn funded_distributor() -> Distributor {
+    let mut distributor = Distributor::new(felt(OWNER));
+    distributor.fund(1000);
+    distributor
+}
+
+/// Tests that users claiming at different times end up with their cumulative amounts
+#[test]
+fn rollout_balances() {
+    let rounds = three_rounds();
+    let mut distributor = funded_distributor();
+
+    distributor
+        .add_root(felt(OWNER), rounds[0].tree.root)
+        .unwrap();
+    assert_eq!(claim(&mut distributor, &rounds[0], "0x1"), Ok(10));
+
+    distributor
+        .add_root(felt(OWNER), rounds[1].tree.root)
+        .unwrap();
+    assert_eq!(claim(&mut distributor, &rounds[1], "0x1"), Ok(10));
+    assert_eq!(claim(&mut distributor, &rounds[1], "0x3"), Ok(5));
+
+    distributor
+        .add_root(felt(OWNER), rounds[2].tree.root)
+        .unwrap();
+    for address in ["0x1", "0x2", "0x3"] {
+        claim(&mut distributor, &rounds[2], address).unwrap();
+    }
+
+    // Everyone has their cumulative amount of the last round
+    for address in ["0x1", "0x2", "0x3"] {
+        let expected = rounds[2].address_amount(felt(address)).unwrap();
+        assert_eq!(distributor.balance_of(felt(address)), expected);
+        assert_eq!(distributor.amount_already_claimed(felt(address)), expected);
+    }
+    assert_eq!(
+        distributor.contract_balance(),
+        1000 - rounds[2].accumulated_total_amount
+    );
+    assert_eq!(distributor.roots().len(), 3);
+    assert_eq!(distributor.events().len(), 6);
+
+    // Claiming again with the same root transfers nothing
+    assert_eq!(claim(&mut distributor, &rounds[2], "0x1"), Ok(0));
+}
+
+/// Tests claims with an old root after a newer one has been added
+#[test]
+fn old_root_after_newer() {
+    let rounds = three_rounds();
+    let mut distributor = funded_distributor();
+    for round in rounds.iter() {
+        distributor.add_root(felt(OWNER), round.tree.root).unwrap();
+    }
+
+    // An address that hasn't claimed can still use an old root and claim the rest later
+    assert_eq!(claim(&mut distributor, &rounds[0], "0x2"), Ok(20));
+    assert_eq!(claim(&mut distributor, &rounds[2], "0x2"), Ok(20));
+
+    // Going back to an older root after a newer claim underflows in the contract
+    assert_eq!(claim(&mut distributor, &rounds[2], "0x1"), Ok(30));
+    assert_eq!(
+        claim(&mut distributor, &rounds[0], "0x1"),
+        Err("u128_sub Overflow".to_string())
+    );
+    // The failed claim changes nothing
+    assert_eq!(distributor.amount_already_claimed(felt("0x1")), 30);
+    assert_eq!(distributor.balance_of(felt("0x1")), 30);
+    assert_eq!(distributor.contract_balance(), 1000 - 70);
+}
+
+/// Tests the failures of the contract
+#[test]
+fn failed_calls() {
+    let rounds = three_rounds();
+    let mut distributor = Distributor::new(felt(OWNER));
+
+    assert_eq!(
+        claim(&mut distributor, &rounds[0], "0x1"),
+        Err("Index out of bounds".to_string())
+    );
+    assert!(distributor
+        .add_root(felt("0x1"), rounds[0].tree.root)
+        .is_err());
+    distributor
+        .add_root(felt(OWNER), rounds[0].tree.root)
+        .unwrap();
+
+    // Not enough tokens in the contract
+    assert_eq!(
+        claim(&mut distributor, &rounds[0], "0x1"),
+        Err("ERC20: insufficient balance".to_string())
+    );
+    distributor.fund(100);
+
+    // Claiming more than allocated, someone else's allocation or a round without a root fails
+    let proof = rounds[0].tree.address_calldata("0x1").unwrap().proof;
+    let proof: Vec<FieldElement> = proof.iter().map(|p| felt(p)).collect();
+    assert_eq!(
+        distributor.claim(felt("0x1"), 11, &proof),
+        Err("INVALID PROOF".to_string())
+    );
+    assert_eq!(
+        distributor.claim(felt("0x2"), 10, &proof),
+        Err("INVALID PROOF".to_string())
+    );
+    assert_eq!(
+        claim(&mut distributor, &rounds[1], "0x3"),
+        Err("INVALID PROOF".to_string())
+    );
+    assert!(distributor.events().is_empty());
+    assert_eq!(distributor.contract_balance(), 100);
+
+    assert_eq!(distributor.claim(felt("0x1"), 10, &proof), Ok(10));
+}
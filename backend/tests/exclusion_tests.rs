@@ -0,0 +1,120 @@
+/// This file tests removing the allocations of denied addresses
+use defispring::api::{
+    exclusions::{read_denylist, ExclusionPolicy},
+    math::mul_div,
+    processor::{map_cumulative_amounts, transform_allocations_to_cumulative_rounds},
+    structs::{CampaignConfig, JSONAllocation, RoundAmounts},
+};
+use serde_json::from_str;
+use starknet_crypto::FieldElement;
+use std::{env, fs, str::FromStr};
+
+fn round_amounts() -> Vec<RoundAmounts> {
+    let round1: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "10"},
+            {"address": "0x2", "amount": "30"},
+            {"address": "0x3", "amount": "7"},
+            {"address": "0x4", "amount": "5"}
+        ]"#,
+    )
+    .unwrap();
+    let round2: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "10"},
+            {"address": "0x2", "amount": "20"},
+            {"address": "0x4", "amount": "5"}
+        ]"#,
+    )
+    .unwrap();
+    vec![
+        RoundAmounts {
+            round: 1,
+            amounts: round1,
+        },
+        RoundAmounts {
+            round: 2,
+            amounts: round2,
+        },
+    ]
+}
+
+fn config(policy: ExclusionPolicy) -> CampaignConfig {
+    CampaignConfig {
+        exclusion_policy: policy,
+        denylist: read_denylist("./tests/test_denylist_input"),
+        ..Default::default()
+    }
+}
+
+/// Tests that denied amounts are dropped, from the given round on
+#[test]
+fn dropped() {
+    let res = map_cumulative_amounts(round_amounts(), &config(ExclusionPolicy::Drop));
+    let key = |a: &str| (FieldElement::from_str(a).unwrap(), None);
+
+    assert!(!res[0].cumulative_amounts.contains_key(&key("0x3")));
+    // Address 4 is denied only from round 2, so it keeps its earlier amount
+    assert_eq!(res[0].cumulative_amounts[&key("0x4")], 5);
+    assert_eq!(res[1].cumulative_amounts[&key("0x4")], 5);
+    assert_eq!(res[1].cumulative_amounts[&key("0x1")], 20);
+    assert_eq!(res[1].cumulative_amounts[&key("0x2")], 50);
+
+    assert_eq!(res[0].exclusions.len(), 1);
+    assert_eq!(res[0].exclusions[0].reason, "sybil");
+    assert_eq!(res[0].exclusions[0].amount, 7);
+    assert!(!res[0].exclusions[0].redistributed);
+    assert_eq!(res[1].exclusions.len(), 1);
+    assert_eq!(res[1].exclusions[0].reason, "exploit contract");
+}
+
+/// Tests that denied amounts are shared pro rata, with the dust going to the largest allocation
+#[test]
+fn redistributed() {
+    let res = transform_allocations_to_cumulative_rounds(
+        round_amounts(),
+        &config(ExclusionPolicy::Redistribute),
+    );
+    let amount = |round: usize, a: &str| {
+        res[round]
+            .address_amount(FieldElement::from_str(a).unwrap())
+            .unwrap()
+    };
+
+    // 7 shared to 10 : 30 : 5 is 1.55 : 4.66 : 0.77, rounded down plus 2 dust to the largest
+    assert_eq!(amount(0, "0x1"), 11);
+    assert_eq!(amount(0, "0x2"), 36);
+    assert_eq!(amount(0, "0x3"), 0);
+    assert_eq!(amount(0, "0x4"), 5);
+    assert_eq!(res[0].round_total_amount, 52);
+
+    // 5 shared to 10 : 20 is 1.66 : 3.33, rounded down plus 1 dust to the largest
+    assert_eq!(amount(1, "0x1"), 22);
+    assert_eq!(amount(1, "0x2"), 60);
+    assert_eq!(res[1].round_total_amount, 35);
+
+    assert!(res[1].exclusions[0].redistributed);
+}
+
+/// Tests that sharing large amounts doesn't overflow
+#[test]
+fn large_amounts() {
+    assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), u128::MAX);
+    assert_eq!(mul_div(u128::MAX, 3, 4), u128::MAX / 4 * 3 + 2);
+    assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 110), 1 << 90);
+    assert_eq!(mul_div(7, 10, 45), 1);
+}
+
+/// Tests that an invalid address in the denylist is reported with the index of its entry
+#[test]
+#[should_panic(expected = "Invalid address in denylist entry 1")]
+fn invalid_denylist_address() {
+    let folder = env::temp_dir().join("defispring_invalid_denylist");
+    fs::create_dir_all(&folder).unwrap();
+    fs::write(
+        folder.join("denylist.json"),
+        r#"[{"address": "0x3"}, {"address": "0xg"}]"#,
+    )
+    .unwrap();
+    read_denylist(folder.to_str().unwrap());
+}
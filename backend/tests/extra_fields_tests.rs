@@ -0,0 +1,115 @@
+/// This file tests leaves with extra fields
+use std::str::FromStr;
+
+use defispring::api::{
+    merkle_tree::{hash, verify_multiproof},
+    processor::map_cumulative_amounts,
+    structs::{CampaignConfig, CumulativeAllocation, JSONAllocation, MerkleTree, RoundAmounts},
+};
+use serde_json::from_str;
+use starknet_crypto::{poseidon_hash, poseidon_hash_many, FieldElement};
+
+/// Tests that extra fields are hashed into the leaf and returned in the calldata
+#[test]
+fn extra_fields_in_leaf() {
+    let token = FieldElement::from_str("0x4718").unwrap();
+    let unlock = FieldElement::from(1700000000_u64);
+
+    let allocations = vec![
+        CumulativeAllocation {
+            address: FieldElement::from_str("0x1").unwrap(),
+            cumulative_amount: 1,
+            extra_fields: vec![token, unlock],
+            ..Default::default()
+        },
+        CumulativeAllocation {
+            address: FieldElement::from_str("0x2").unwrap(),
+            cumulative_amount: 2,
+            ..Default::default()
+        },
+    ];
+
+    let mt = MerkleTree::new(allocations);
+
+    let first = mt.address_calldata("0x1").unwrap();
+    assert_eq!(first.extra_fields, vec!["0x4718", "0x6553f100"]);
+    let second = mt.address_calldata("0x2").unwrap();
+    assert!(second.extra_fields.is_empty());
+
+    // Leaf with extra fields is poseidon over the span [address, amount, 0, 2, extra fields...]
+    let first_leaf = poseidon_hash_many(&[
+        FieldElement::from(1_u64),
+        FieldElement::from(1_u64),
+        FieldElement::ZERO,
+        FieldElement::from(2_u64),
+        token,
+        unlock,
+    ]);
+    let second_leaf = poseidon_hash(FieldElement::from(2_u64), FieldElement::from(2_u64));
+    assert_eq!(mt.root, hash(&first_leaf, &second_leaf));
+
+    let calldata = mt
+        .multiproof_calldata(&["0x1".to_string(), "0x2".to_string()])
+        .unwrap();
+    assert!(verify_multiproof(&mt.hasher, &mt.root, &calldata));
+
+    // Changing an extra field breaks the proof
+    let mut hacked = calldata.clone();
+    hacked.extra_fields[0][1] = "0x0".to_string();
+    assert!(!verify_multiproof(&mt.hasher, &mt.root, &hacked));
+}
+
+/// Tests that extra fields can be given in the JSON input, that the latest given ones are used
+/// and that rows with invalid extra fields are skipped
+#[test]
+fn extra_fields_from_json() {
+    let round1: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "5", "extra_fields": ["0x10", "100"]},
+            {"address": "0x2", "amount": "6"}
+        ]"#,
+    )
+    .unwrap();
+    let round2: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "1"},
+            {"address": "0x2", "amount": "1", "extra_fields": ["0x20"]},
+            {"address": "0x2", "amount": "7", "extra_fields": ["not_a_field"]}
+        ]"#,
+    )
+    .unwrap();
+
+    let res = map_cumulative_amounts(
+        vec![
+            RoundAmounts {
+                round: 1,
+                amounts: round1,
+            },
+            RoundAmounts {
+                round: 2,
+                amounts: round2,
+            },
+        ],
+        &CampaignConfig::default(),
+    );
+
+    let one = FieldElement::from(1_u64);
+    let two = FieldElement::from(2_u64);
+
+    assert_eq!(
+        res[0].extra_fields[&(one, None)],
+        vec![FieldElement::from(16_u64), FieldElement::from(100_u64)]
+    );
+    assert!(!res[0].extra_fields.contains_key(&(two, None)));
+
+    // Address 1 keeps its fields, address 2 gets new ones
+    assert_eq!(
+        res[1].extra_fields[&(one, None)],
+        vec![FieldElement::from(16_u64), FieldElement::from(100_u64)]
+    );
+    assert_eq!(
+        res[1].extra_fields[&(two, None)],
+        vec![FieldElement::from(32_u64)]
+    );
+    assert_eq!(res[1].round_amounts[&(two, None)], 1);
+}
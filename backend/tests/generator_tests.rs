@@ -0,0 +1,204 @@
+/// This file tests the fixture generator
+use defispring::api::{
+    address::Address,
+    generator::{
+        generate_allocations, generate_rounds, run_generate, write_fixture, write_rounds,
+        AmountDistribution, GeneratorConfig,
+    },
+    processor::read_allocations,
+};
+use std::{collections::HashSet, env, fs, str::FromStr};
+
+/// Tests that the rows are the ones of the TypeScript generator
+#[test]
+fn generated_rows() {
+    let rows = generate_allocations(3);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows[0].address,
+        "0x0000000000000000000000000000000000000000000000000000000000000001"
+    );
+    assert_eq!(rows[0].amount, "1010");
+    assert_eq!(rows[2].amount, "1030");
+    assert!(generate_allocations(0).is_empty());
+}
+
+/// Tests that the written files are identical on every run and can be read as input
+#[test]
+fn deterministic_fixture() {
+    let folder = env::temp_dir().join("defispring_generator_test");
+    write_fixture(&folder, 2, 100).unwrap();
+    let first = fs::read(folder.join("raw_2.zip")).unwrap();
+    write_fixture(&folder, 2, 100).unwrap();
+    let second = fs::read(folder.join("raw_2.zip")).unwrap();
+    assert_eq!(first, second);
+
+    let rounds = read_allocations(folder.to_str().unwrap().to_string());
+    fs::remove_dir_all(&folder).unwrap();
+
+    // 1000 * 100 + 10 * (1 + ... + 100) per round
+    assert_eq!(rounds.len(), 2);
+    assert_eq!(rounds[0].round_total_amount, 150_500);
+    assert_eq!(rounds[1].accumulated_total_amount, 301_000);
+    assert_eq!(rounds[1].tree.allocations.len(), 100);
+}
+
+/// Tests that the seed decides the files
+#[test]
+fn seeded_rounds() {
+    let config = GeneratorConfig {
+        seed: 7,
+        rounds: 2,
+        addresses: 50,
+        ..Default::default()
+    };
+    let first = env::temp_dir().join("defispring_seeded_test_1");
+    let second = env::temp_dir().join("defispring_seeded_test_2");
+    write_rounds(&first, &config).unwrap();
+    write_rounds(&second, &config).unwrap();
+    let same =
+        fs::read(first.join("raw_2.zip")).unwrap() == fs::read(second.join("raw_2.zip")).unwrap();
+
+    write_rounds(&second, &GeneratorConfig { seed: 8, ..config }).unwrap();
+    let other =
+        fs::read(first.join("raw_2.zip")).unwrap() == fs::read(second.join("raw_2.zip")).unwrap();
+
+    let rounds = read_allocations(first.to_str().unwrap().to_string());
+    fs::remove_dir_all(&first).unwrap();
+    fs::remove_dir_all(&second).unwrap();
+
+    assert!(same);
+    assert!(!other);
+    assert_eq!(rounds.len(), 2);
+    assert_eq!(rounds[0].tree.allocations.len(), 50);
+}
+
+/// Tests the shared addresses of consecutive rounds and that all rows are valid without malformed rows
+#[test]
+fn overlapping_rounds() {
+    let addresses = |overlap: f64| -> Vec<HashSet<String>> {
+        let config = GeneratorConfig {
+            rounds: 3,
+            addresses: 200,
+            overlap,
+            ..Default::default()
+        };
+        generate_rounds(&config)
+            .unwrap()
+            .iter()
+            .map(|r| r.amounts.iter().map(|a| a.address.clone()).collect())
+            .collect()
+    };
+
+    let rounds = addresses(0.25);
+    assert_eq!(rounds[0].intersection(&rounds[1]).count(), 50);
+    assert_eq!(rounds[1].intersection(&rounds[2]).count(), 50);
+    assert!(rounds
+        .iter()
+        .flatten()
+        .all(|a| Address::from_str(a).is_ok()));
+
+    let rounds = addresses(1.0);
+    assert_eq!(rounds[0], rounds[2]);
+    let rounds = addresses(0.0);
+    assert_eq!(rounds[0].intersection(&rounds[1]).count(), 0);
+}
+
+/// Tests the bounds and the skew of the amounts
+#[test]
+fn amount_distributions() {
+    let amounts = |distribution: AmountDistribution| -> Vec<u128> {
+        let config = GeneratorConfig {
+            addresses: 2000,
+            distribution,
+            min_amount: 100,
+            max_amount: 100_000,
+            ..Default::default()
+        };
+        let mut amounts: Vec<u128> = generate_rounds(&config).unwrap()[0]
+            .amounts
+            .iter()
+            .map(|a| a.amount.parse().unwrap())
+            .collect();
+        amounts.sort();
+        amounts
+    };
+
+    for distribution in [AmountDistribution::Uniform, AmountDistribution::Pareto] {
+        let amounts = amounts(distribution);
+        assert!(amounts.iter().all(|a| (100..=100_000).contains(a)));
+    }
+
+    // Most Pareto amounts are close to the minimum, while uniform amounts are around the middle
+    let pareto = amounts(AmountDistribution::Pareto);
+    assert!(pareto[1000] < 300);
+    let uniform = amounts(AmountDistribution::Uniform);
+    assert!(uniform[1000] > 40_000 && uniform[1000] < 60_000);
+}
+
+/// Tests that malformed rows are injected and skipped when the files are loaded
+#[test]
+fn malformed_rows() {
+    let config = GeneratorConfig {
+        addresses: 1000,
+        malformed: 0.1,
+        ..Default::default()
+    };
+    let rounds = generate_rounds(&config).unwrap();
+    let malformed = rounds[0]
+        .amounts
+        .iter()
+        .filter(|a| Address::from_str(&a.address).is_err() || a.amount.parse::<u128>().is_err())
+        .count();
+    assert!(malformed > 50 && malformed < 150);
+
+    let folder = env::temp_dir().join("defispring_malformed_test");
+    write_rounds(&folder, &config).unwrap();
+    let path = folder.to_str().unwrap().to_string();
+    let loaded = read_allocations(path);
+    fs::remove_dir_all(&folder).unwrap();
+    let valid_addresses: HashSet<Address> = rounds[0]
+        .amounts
+        .iter()
+        .filter_map(|a| Address::from_str(&a.address).ok())
+        .collect();
+    assert_eq!(loaded.len(), rounds.len());
+    assert_eq!(loaded[0].tree.allocations.len(), valid_addresses.len());
+}
+
+/// Tests the options of the generate subcommand
+#[test]
+fn generate_arguments() {
+    let args = |values: &[&str]| -> Vec<String> { values.iter().map(|v| v.to_string()).collect() };
+
+    assert!(run_generate(&args(&[])).is_err());
+    assert!(run_generate(&args(&["--seed", "1"])).is_err());
+    assert!(run_generate(&args(&["./out", "--seed"])).is_err());
+    assert!(run_generate(&args(&["./out", "--seed", "many"])).is_err());
+    assert!(run_generate(&args(&["./out", "--size", "10"])).is_err());
+    assert!(run_generate(&args(&["./out", "--distribution", "normal"])).is_err());
+    assert!(run_generate(&args(&["./out", "--rounds", "0"])).is_err());
+    assert!(run_generate(&args(&["./out", "--overlap", "1.5"])).is_err());
+    assert!(run_generate(&args(&["./out", "--min-amount", "10", "--max-amount", "5"])).is_err());
+
+    let folder = env::temp_dir().join("defispring_generate_args_test");
+    run_generate(&args(&[
+        folder.to_str().unwrap(),
+        "--seed",
+        "3",
+        "--rounds",
+        "3",
+        "--addresses",
+        "10",
+        "--distribution",
+        "pareto",
+    ]))
+    .unwrap();
+    let mut files: Vec<String> = fs::read_dir(&folder)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    fs::remove_dir_all(&folder).unwrap();
+    files.sort();
+    assert_eq!(files, vec!["raw_1.zip", "raw_2.zip", "raw_3.zip"]);
+}
@@ -0,0 +1,225 @@
+/// This file pins the hashes, roots and proofs of the trees to fixed vectors shared with the contract tests.
+/// Run with UPDATE_GOLDEN_VECTORS=1 to regenerate the vectors after an intended change
+use defispring::api::{
+    distributor::Distributor,
+    math::felt_to_u128,
+    merkle_tree::felt_to_b16,
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use serde::Serialize;
+use serde_json::Value;
+use starknet_crypto::{pedersen_hash, poseidon_hash, FieldElement};
+use std::{env, fs};
+
+mod common;
+use common::felt;
+
+/// Read by the snforge tests of the contract
+const VECTORS_FILE: &str = "../contract/data/golden_vectors.json";
+/// Trees of size 1, 2, 3, odd sizes and powers of two
+const TREE_SIZES: [u64; 7] = [1, 2, 3, 4, 5, 7, 8];
+
+#[derive(Serialize)]
+struct GoldenVectors {
+    /// poseidon(address, amount)
+    leaf_hashes: Vec<LeafVector>,
+    /// pedersen of the pair with the smaller value first
+    pair_hashes: Vec<PairVector>,
+    trees: Vec<TreeVector>,
+}
+
+#[derive(Serialize)]
+struct LeafVector {
+    address: String,
+    amount: String,
+    leaf: String,
+}
+
+#[derive(Serialize)]
+struct PairVector {
+    a: String,
+    b: String,
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct TreeVector {
+    size: u64,
+    root: String,
+    /// The hashes of each level from the leaves up. Odd levels are padded by duplicating the last hash
+    levels: Vec<Vec<String>>,
+    proofs: Vec<ProofVector>,
+}
+
+#[derive(Serialize)]
+struct ProofVector {
+    address: String,
+    amount: String,
+    proof: Vec<String>,
+}
+
+fn allocation(i: u64) -> CumulativeAllocation {
+    // One amount close to the u128 limit to cover large values
+    let amount = match i {
+        6 => u128::MAX - 1,
+        _ => (i as u128 + 1) * 1_000_000_000_000_000_000 + i as u128,
+    };
+    CumulativeAllocation {
+        address: FieldElement::from(0x10000 + i * 0x1111),
+        cumulative_amount: amount,
+        token: None,
+        extra_fields: vec![],
+    }
+}
+
+fn generate() -> GoldenVectors {
+    let leaf_hashes = (0..4)
+        .map(allocation)
+        .map(|a| LeafVector {
+            address: felt_to_b16(&a.address),
+            amount: felt_to_b16(&FieldElement::from(a.cumulative_amount)),
+            leaf: felt_to_b16(&poseidon_hash(
+                a.address,
+                FieldElement::from(a.cumulative_amount),
+            )),
+        })
+        .collect();
+
+    let (small, large) = (felt("0x1234"), felt("0x5678"));
+    let pair_hashes = [(small, large), (large, small), (small, small)]
+        .iter()
+        .map(|(a, b)| PairVector {
+            a: felt_to_b16(a),
+            b: felt_to_b16(b),
+            hash: felt_to_b16(&match a < b {
+                true => pedersen_hash(a, b),
+                false => pedersen_hash(b, a),
+            }),
+        })
+        .collect();
+
+    let trees = TREE_SIZES
+        .iter()
+        .map(|size| {
+            let allocations: Vec<CumulativeAllocation> = (0..*size).map(allocation).collect();
+            let tree = MerkleTree::new(allocations.clone());
+            let proofs = allocations
+                .iter()
+                .map(|a| {
+                    let address = felt_to_b16(&a.address);
+                    let calldata = tree.address_calldata(&address).unwrap();
+                    ProofVector {
+                        address,
+                        amount: calldata.amount,
+                        proof: calldata.proof,
+                    }
+                })
+                .collect();
+            TreeVector {
+                size: *size,
+                root: felt_to_b16(&tree.root),
+                levels: tree
+                    .levels
+                    .iter()
+                    .map(|l| l.iter().map(felt_to_b16).collect())
+                    .collect(),
+                proofs,
+            }
+        })
+        .collect();
+
+    GoldenVectors {
+        leaf_hashes,
+        pair_hashes,
+        trees,
+    }
+}
+
+/// Tests that the trees still give the stored vectors
+#[test]
+fn vectors_match_file() {
+    let generated = serde_json::to_value(generate()).unwrap();
+    if env::var("UPDATE_GOLDEN_VECTORS").is_ok() {
+        let contents = serde_json::to_string_pretty(&generated).unwrap();
+        fs::write(VECTORS_FILE, contents + "\n").unwrap();
+    }
+
+    let stored: Value = serde_json::from_slice(&fs::read(VECTORS_FILE).unwrap()).unwrap();
+    assert_eq!(generated, stored);
+}
+
+/// Tests that every stored proof verifies with the claim logic of the contract
+#[test]
+fn proofs_verify_like_the_contract() {
+    let distributor = Distributor::new(FieldElement::ZERO);
+    let stored: Value = serde_json::from_slice(&fs::read(VECTORS_FILE).unwrap()).unwrap();
+
+    let trees = stored["trees"].as_array().unwrap();
+    assert_eq!(trees.len(), TREE_SIZES.len());
+    for tree in trees {
+        let root = felt(tree["root"].as_str().unwrap());
+        let proofs = tree["proofs"].as_array().unwrap();
+        assert_eq!(proofs.len() as u64, tree["size"].as_u64().unwrap());
+        for proof in proofs {
+            let amount = felt_to_u128(&felt(proof["amount"].as_str().unwrap())).unwrap();
+            let hashes: Vec<FieldElement> = proof["proof"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| felt(p.as_str().unwrap()))
+                .collect();
+            let address = felt(proof["address"].as_str().unwrap());
+            assert_eq!(distributor.get_root_for(address, amount, &hashes), root);
+        }
+    }
+
+    // A single leaf is paired with itself
+    let single = &trees[0]["levels"];
+    assert_eq!(single[0][0], single[0][1]);
+}
+
+fn tree(leaves: &[(&str, u128)]) -> MerkleTree {
+    MerkleTree::new(
+        leaves
+            .iter()
+            .map(|(address, amount)| CumulativeAllocation {
+                address: felt(address),
+                cumulative_amount: *amount,
+                token: None,
+                extra_fields: vec![],
+            })
+            .collect(),
+    )
+}
+
+/// Tests that the backend gives the roots and proofs used in the snforge tests of the contract
+#[test]
+fn contract_test_claims() {
+    let distributor = Distributor::new(FieldElement::ZERO);
+
+    let two_leaves = tree(&[("0x13", 0x88), ("0x21", 0xd5)]);
+    assert_eq!(
+        felt_to_b16(&two_leaves.root),
+        "0xf7c8d3f309262572ad35df8ff6c33f24d8114c60eac3bc27bf42382ca82faf"
+    );
+    let calldata = two_leaves.address_calldata("0x13").unwrap();
+    assert_eq!(
+        calldata.proof,
+        vec!["0x2a18afb0550a011d54ca3940648e59894c06e4c3d0a611256c0b575bd528b3b"]
+    );
+    assert_eq!(
+        distributor.get_root_for(felt("0x13"), 0x88, &[felt(&calldata.proof[0])]),
+        two_leaves.root
+    );
+
+    // A single leaf is paired with itself, so its proof is the leaf
+    let single_leaf = tree(&[("0x14", 0x89)]);
+    assert_eq!(
+        felt_to_b16(&single_leaf.root),
+        "0x3af4d227c0978ff30099df450f64676ef25f9255d4fa36f900be2aed17f332d"
+    );
+    assert_eq!(
+        single_leaf.address_calldata("0x14").unwrap().proof,
+        vec!["0x7fa669b18489a1632df0de6e4d2b58558457b10fbcdeae8975e7e4d8d2e15db"]
+    );
+}
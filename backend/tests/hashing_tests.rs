@@ -0,0 +1,129 @@
+/// This file tests the different hash schemes of the trees
+use defispring::api::{
+    hashing::{DoubleHashedLeafHasher, HashScheme, PoseidonNodeHasher, StandardHasher, TreeHasher},
+    merkle_tree::verify_multiproof,
+    processor::read_campaign_config,
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use starknet_crypto::{pedersen_hash, poseidon_hash, poseidon_hash_single, FieldElement};
+
+fn allocations() -> Vec<CumulativeAllocation> {
+    (1..=5_u64)
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i),
+            cumulative_amount: (i * 10) as u128,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Checks that every address of the tree has a valid proof
+fn all_proofs_valid<H: TreeHasher>(mt: &MerkleTree<H>) -> bool {
+    mt.allocations.iter().all(|a| {
+        let calldata = mt
+            .multiproof_calldata(&[format!("{:#x}", a.address)])
+            .unwrap();
+        verify_multiproof(&mt.hasher, &mt.root, &calldata)
+    })
+}
+
+/// Tests that the default tree uses the standard scheme of the contract
+#[test]
+fn standard_scheme_is_default() {
+    let default = MerkleTree::new(allocations());
+    let standard = MerkleTree::with_hasher(allocations(), StandardHasher);
+
+    assert_eq!(default.root, standard.root);
+    assert_eq!(default.hasher, HashScheme::Standard);
+}
+
+/// Tests that a token isn't hashed like an extra field with the same value
+#[test]
+fn token_and_extra_fields_differ() {
+    let token = FieldElement::from(0x4718_u64);
+    let with_token = CumulativeAllocation {
+        address: FieldElement::ONE,
+        cumulative_amount: 5,
+        token: Some(token),
+        ..Default::default()
+    };
+    let with_extra_field = CumulativeAllocation {
+        token: None,
+        extra_fields: vec![token],
+        ..with_token.clone()
+    };
+
+    assert_ne!(
+        StandardHasher.hash_leaf(&with_token),
+        StandardHasher.hash_leaf(&with_extra_field)
+    );
+}
+
+/// Tests that each scheme hashes as documented and produces valid proofs
+#[test]
+fn schemes_hash_leaves_and_nodes() {
+    let a = FieldElement::from(1_u64);
+    let b = FieldElement::from(2_u64);
+    let allocation = CumulativeAllocation {
+        address: a,
+        cumulative_amount: 2,
+        ..Default::default()
+    };
+    let (low, high) = if a < b { (a, b) } else { (b, a) };
+
+    assert_eq!(StandardHasher.hash_leaf(&allocation), poseidon_hash(a, b));
+    assert_eq!(StandardHasher.hash_node(&b, &a), pedersen_hash(&low, &high));
+
+    assert_eq!(
+        PoseidonNodeHasher.hash_leaf(&allocation),
+        poseidon_hash(a, b)
+    );
+    assert_eq!(
+        PoseidonNodeHasher.hash_node(&b, &a),
+        poseidon_hash(low, high)
+    );
+
+    assert_eq!(
+        DoubleHashedLeafHasher.hash_leaf(&allocation),
+        poseidon_hash_single(poseidon_hash(a, b))
+    );
+    assert_eq!(
+        DoubleHashedLeafHasher.hash_node(&b, &a),
+        pedersen_hash(&low, &high)
+    );
+}
+
+/// Tests that the schemes give different roots and that all proofs verify with the tree's own scheme
+#[test]
+fn schemes_give_different_valid_trees() {
+    let standard = MerkleTree::with_hasher(allocations(), HashScheme::Standard);
+    let poseidon = MerkleTree::with_hasher(allocations(), HashScheme::PoseidonNode);
+    let double = MerkleTree::with_hasher(allocations(), HashScheme::DoubleHashedLeaf);
+
+    assert_ne!(standard.root, poseidon.root);
+    assert_ne!(standard.root, double.root);
+    assert_ne!(poseidon.root, double.root);
+
+    assert!(all_proofs_valid(&standard));
+    assert!(all_proofs_valid(&poseidon));
+    assert!(all_proofs_valid(&double));
+
+    // A proof doesn't verify with another scheme
+    let calldata = poseidon.multiproof_calldata(&["0x1".to_string()]).unwrap();
+    assert!(!verify_multiproof(
+        &HashScheme::Standard,
+        &poseidon.root,
+        &calldata
+    ));
+}
+
+/// Tests that the scheme is read from the campaign config
+#[test]
+fn campaign_config() {
+    let config = read_campaign_config("./tests/test_campaign_config");
+    assert_eq!(config.hash_scheme, HashScheme::PoseidonNode);
+
+    // Missing config falls back to the defaults
+    let config = read_campaign_config("./tests/test_data_input_files/valid");
+    assert_eq!(config.hash_scheme, HashScheme::Standard);
+}
@@ -0,0 +1,119 @@
+/// This file tests the states of rounds and which rounds are served in each of them
+use defispring::api::{
+    lifecycle::{
+        check_transition, read_round_states, round_state, served_rounds, write_round_states,
+    },
+    structs::{CampaignConfig, RoundState, RoundTreeData, Visibility},
+};
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
+
+mod common;
+use common::numbered_rounds;
+
+fn served(rounds: &[RoundTreeData], config: &CampaignConfig, visibility: Visibility) -> Vec<u8> {
+    served_rounds(rounds, config, visibility)
+        .iter()
+        .map(|r| r.round)
+        .collect()
+}
+
+/// Tests the states derived from the file and the published roots, and the rounds served in each
+#[test]
+fn states_and_served_rounds() {
+    let rounds = numbered_rounds(&[1, 2, 3, 4, 5], &CampaignConfig::default());
+    let config = CampaignConfig {
+        round_states: BTreeMap::from([
+            (1, RoundState::Approved),
+            (2, RoundState::Approved),
+            (3, RoundState::Validated),
+            (5, RoundState::Retired),
+        ]),
+        published_roots: vec![rounds[0].tree.root, rounds[4].tree.root],
+        ..Default::default()
+    };
+    let states: Vec<RoundState> = rounds.iter().map(|r| round_state(r, &config)).collect();
+    assert_eq!(
+        states,
+        vec![
+            RoundState::Published,
+            // The root in slot 1 isn't the root of round 2
+            RoundState::Approved,
+            RoundState::Validated,
+            RoundState::Draft,
+            RoundState::Retired,
+        ]
+    );
+    assert_eq!(served(&rounds, &config, Visibility::Served), vec![1, 2]);
+    assert_eq!(
+        served(&rounds, &config, Visibility::Preview),
+        vec![1, 2, 3, 4]
+    );
+}
+
+/// Tests which changes of state operators can make
+#[test]
+fn transitions() {
+    use RoundState::*;
+    assert!(check_transition(Validated, Approved).is_ok());
+    for from in [Draft, Validated, Approved, Published] {
+        assert!(check_transition(from, Retired).is_ok());
+    }
+    assert_eq!(
+        check_transition(Draft, Approved).unwrap_err(),
+        "A round can't go from draft to approved"
+    );
+    assert!(check_transition(Retired, Approved).is_err());
+    assert!(check_transition(Retired, Retired).is_err());
+    assert!(check_transition(Approved, Validated).is_err());
+    assert_eq!(
+        check_transition(Approved, Published).unwrap_err(),
+        "A round can't go from approved to published"
+    );
+}
+
+/// Tests that the states are read back as written, and that a missing file means no states
+#[test]
+fn states_file() {
+    let folder = env::temp_dir().join("defispring_round_states");
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    let folder = folder.to_str().unwrap();
+    assert!(read_round_states(folder).is_empty());
+
+    let states = BTreeMap::from([(1, RoundState::Published), (2, RoundState::Retired)]);
+    write_round_states(folder, &states).unwrap();
+    assert_eq!(read_round_states(folder), states);
+    let contents = fs::read_to_string(format!("{}/round_states.json", folder)).unwrap();
+    assert!(contents.contains("\"2\": \"retired\""));
+    assert!(!PathBuf::from(folder).join("round_states.json.tmp").exists());
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+/// Tests that the rounds disabled by earlier versions are read as retired
+#[test]
+fn disabled_rounds_file() {
+    let folder = env::temp_dir().join("defispring_disabled_rounds");
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    let folder = folder.to_str().unwrap();
+
+    fs::write(format!("{}/disabled_rounds.json", folder), "[2, 3]").unwrap();
+    assert_eq!(
+        read_round_states(folder),
+        BTreeMap::from([(2, RoundState::Retired), (3, RoundState::Retired)])
+    );
+
+    let states = BTreeMap::from([(1, RoundState::Approved), (2, RoundState::Approved)]);
+    write_round_states(folder, &states).unwrap();
+    assert_eq!(
+        read_round_states(folder),
+        BTreeMap::from([
+            (1, RoundState::Approved),
+            (2, RoundState::Retired),
+            (3, RoundState::Retired)
+        ])
+    );
+
+    fs::remove_dir_all(folder).unwrap();
+}
@@ -0,0 +1,190 @@
+/// This file tests the caps and the minimum claim of a campaign
+use defispring::api::{
+    limits::CapPolicy,
+    structs::{CampaignConfig, LimitKind, RoundTreeData},
+};
+use serde_json::from_str;
+
+mod common;
+use common::{felt, rounds};
+
+fn amount(round: &RoundTreeData, address: &str) -> u128 {
+    round.address_amount(felt(address)).unwrap()
+}
+
+/// Tests that the amounts are read from the campaign config as strings
+#[test]
+fn config_amounts() {
+    let config: CampaignConfig = from_str(
+        r#"{"round_cap": "340282366920938463463374607431768211455", "min_claim": "10", "cap_policy": "redistribute"}"#,
+    )
+    .unwrap();
+    assert_eq!(config.round_cap, Some(u128::MAX));
+    assert_eq!(config.cumulative_cap, None);
+    assert_eq!(config.min_claim, Some(10));
+    assert_eq!(config.cap_policy, CapPolicy::Redistribute);
+
+    assert!(from_str::<CampaignConfig>(r#"{"round_cap": "-1"}"#).is_err());
+}
+
+/// Tests that amounts above the round cap are burned and reported
+#[test]
+fn round_cap_burned() {
+    let config = CampaignConfig {
+        round_cap: Some(50),
+        ..Default::default()
+    };
+    let res = rounds(&[&[("0x1", "100"), ("0x2", "30")]], &config);
+
+    assert_eq!(amount(&res[0], "0x1"), 50);
+    assert_eq!(amount(&res[0], "0x2"), 30);
+    assert_eq!(res[0].burned_amount, 50);
+    assert_eq!(res[0].round_total_amount, 80);
+    assert_eq!(res[0].limits.len(), 1);
+    assert_eq!(res[0].limits[0].kind, LimitKind::RoundCap);
+    assert_eq!(res[0].limits[0].amount, 50);
+}
+
+/// Tests that capped amounts are shared among the addresses below the cap without pushing them over it
+#[test]
+fn round_cap_redistributed() {
+    let config = CampaignConfig {
+        round_cap: Some(50),
+        cap_policy: CapPolicy::Redistribute,
+        ..Default::default()
+    };
+    let res = rounds(&[&[("0x1", "100"), ("0x2", "30"), ("0x3", "10")]], &config);
+
+    assert_eq!(amount(&res[0], "0x1"), 50);
+    assert_eq!(amount(&res[0], "0x2"), 50);
+    assert_eq!(amount(&res[0], "0x3"), 40);
+    assert_eq!(res[0].burned_amount, 0);
+    assert_eq!(res[0].round_total_amount, 140);
+
+    // Nothing can be shared when everyone is at the cap
+    let res = rounds(&[&[("0x1", "100"), ("0x2", "60")]], &config);
+    assert_eq!(res[0].burned_amount, 60);
+}
+
+/// Tests that an address can't get more than the cumulative cap over all rounds
+#[test]
+fn cumulative_cap() {
+    let config = CampaignConfig {
+        cumulative_cap: Some(60),
+        ..Default::default()
+    };
+    let res = rounds(
+        &[
+            &[("0x1", "50")],
+            &[("0x1", "30")],
+            &[("0x1", "5"), ("0x2", "5")],
+        ],
+        &config,
+    );
+
+    assert_eq!(amount(&res[0], "0x1"), 50);
+    assert_eq!(amount(&res[1], "0x1"), 60);
+    assert_eq!(amount(&res[2], "0x1"), 60);
+    assert_eq!(res[1].limits[0].kind, LimitKind::CumulativeCap);
+    assert_eq!(res[1].limits[0].amount, 20);
+    assert_eq!(res[2].burned_amount, 5);
+}
+
+/// Tests that amounts below the minimum claim are carried forward until they reach it
+#[test]
+fn min_claim_carried_forward() {
+    let config = CampaignConfig {
+        min_claim: Some(10),
+        ..Default::default()
+    };
+    let res = rounds(
+        &[
+            &[("0x1", "4"), ("0x2", "20")],
+            &[("0x1", "4")],
+            &[("0x1", "3")],
+        ],
+        &config,
+    );
+
+    assert_eq!(amount(&res[0], "0x1"), 0);
+    assert_eq!(res[0].tree.allocations.len(), 1);
+    assert_eq!(res[0].limits[0].kind, LimitKind::BelowMinClaim);
+    assert_eq!(res[0].limits[0].amount, 4);
+
+    assert_eq!(amount(&res[1], "0x1"), 0);
+    assert_eq!(res[1].limits[0].amount, 8);
+
+    assert_eq!(amount(&res[2], "0x1"), 11);
+    assert!(res[2].limits.is_empty());
+    assert_eq!(res[2].round_total_amount, 11);
+    assert_eq!(res[2].accumulated_total_amount, 31);
+}
+
+/// Tests that the round cap also covers the carried amounts released in a round
+#[test]
+fn round_cap_with_carried_amounts() {
+    let config = CampaignConfig {
+        round_cap: Some(50),
+        min_claim: Some(10),
+        ..Default::default()
+    };
+    let res = rounds(&[&[("0x1", "5"), ("0x2", "20")], &[("0x1", "50")]], &config);
+
+    assert_eq!(amount(&res[1], "0x1"), 50);
+    assert_eq!(res[1].round_total_amount, 50);
+    assert_eq!(res[1].burned_amount, 5);
+    assert_eq!(res[1].limits[0].kind, LimitKind::RoundCap);
+    assert_eq!(res[1].limits[0].amount, 5);
+}
+
+/// Tests that carried amounts of addresses missing from a round are still reported,
+/// and released once they reach the minimum claim
+#[test]
+fn carried_amounts_without_allocations() {
+    let config = CampaignConfig {
+        round_cap: Some(50),
+        min_claim: Some(10),
+        cap_policy: CapPolicy::Redistribute,
+        ..Default::default()
+    };
+    let res = rounds(
+        &[
+            &[("0x1", "4"), ("0x2", "20")],
+            &[("0x2", "20")],
+            &[("0x2", "100")],
+        ],
+        &config,
+    );
+
+    assert_eq!(res[1].limits.len(), 1);
+    assert_eq!(res[1].limits[0].kind, LimitKind::BelowMinClaim);
+    assert_eq!(res[1].limits[0].amount, 4);
+    assert_eq!(amount(&res[1], "0x1"), 0);
+
+    // The 50 cut from 0x2 are shared with 0x1, which has only its carried 4 in the round
+    assert_eq!(amount(&res[2], "0x1"), 50);
+    assert_eq!(amount(&res[2], "0x2"), 90);
+    assert_eq!(res[2].burned_amount, 4);
+}
+
+/// Tests that small amounts aren't carried once the address has been released the minimum claim
+#[test]
+fn min_claim_reached_before() {
+    let config = CampaignConfig {
+        min_claim: Some(10),
+        ..Default::default()
+    };
+    let res = rounds(
+        &[
+            &[("0x1", "6"), ("0x2", "20")],
+            &[("0x1", "6")],
+            &[("0x1", "1")],
+        ],
+        &config,
+    );
+
+    assert_eq!(amount(&res[0], "0x1"), 0);
+    assert_eq!(amount(&res[1], "0x1"), 12);
+    assert_eq!(amount(&res[2], "0x1"), 13);
+    assert!(res[2].limits.is_empty());
+}
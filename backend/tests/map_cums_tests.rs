@@ -1,7 +1,7 @@
 /// This file tests the "map_cumulative_amounts" function
 use defispring::api::{
     processor::map_cumulative_amounts,
-    structs::{JSONAllocation, RoundAmounts},
+    structs::{CampaignConfig, JSONAllocation, RoundAmounts},
 };
 use starknet_crypto::FieldElement;
 use std::str::FromStr;
@@ -15,7 +15,7 @@ fn test_empty_data() {
         round: 1u8,
         amounts: drop,
     });
-    let res = map_cumulative_amounts(round_data);
+    let res = map_cumulative_amounts(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 1);
     assert!(res[0].cumulative_amounts.len() == 0);
@@ -33,14 +33,17 @@ fn test_cumulative_one_round() {
     drop.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -48,20 +51,20 @@ fn test_cumulative_one_round() {
         round: 1u8,
         amounts: drop,
     });
-    let res = map_cumulative_amounts(round_data);
+    let res = map_cumulative_amounts(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 1);
     assert!(res[0].cumulative_amounts.len() == 3);
     assert!(res[0].round_amounts.len() == 3);
     assert!(res[0].round == 1_u8);
 
-    assert!(res[0].cumulative_amounts[&one] == 5_u128);
-    assert!(res[0].cumulative_amounts[&two] == 6_u128);
-    assert!(res[0].cumulative_amounts[&three] == 7_u128);
+    assert!(res[0].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].cumulative_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].cumulative_amounts[&(three, None)] == 7_u128);
 
-    assert!(res[0].round_amounts[&one] == 5_u128);
-    assert!(res[0].round_amounts[&two] == 6_u128);
-    assert!(res[0].round_amounts[&three] == 7_u128);
+    assert!(res[0].round_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].round_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].round_amounts[&(three, None)] == 7_u128);
 }
 
 #[test]
@@ -75,18 +78,22 @@ fn test_cumulative_two_rounds() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop2.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "23".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -98,7 +105,7 @@ fn test_cumulative_two_rounds() {
         round: 2u8,
         amounts: drop2,
     });
-    let res = map_cumulative_amounts(round_data);
+    let res = map_cumulative_amounts(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 2);
 
@@ -110,21 +117,21 @@ fn test_cumulative_two_rounds() {
     assert!(res[1].round_amounts.len() == 1);
     assert!(res[1].round == 2_u8);
 
-    assert!(res[0].cumulative_amounts[&one] == 5_u128);
-    assert!(res[0].cumulative_amounts[&two] == 6_u128);
-    assert!(res[0].cumulative_amounts[&three] == 7_u128);
+    assert!(res[0].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].cumulative_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].cumulative_amounts[&(three, None)] == 7_u128);
 
-    assert!(res[0].round_amounts[&one] == 5_u128);
-    assert!(res[0].round_amounts[&two] == 6_u128);
-    assert!(res[0].round_amounts[&three] == 7_u128);
+    assert!(res[0].round_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].round_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].round_amounts[&(three, None)] == 7_u128);
 
-    assert!(res[1].cumulative_amounts[&one] == 5_u128);
-    assert!(res[1].cumulative_amounts[&two] == 6_u128);
-    assert!(res[1].cumulative_amounts[&three] == 30_u128);
+    assert!(res[1].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[1].cumulative_amounts[&(two, None)] == 6_u128);
+    assert!(res[1].cumulative_amounts[&(three, None)] == 30_u128);
 
-    assert!(!res[1].round_amounts.contains_key(&one));
-    assert!(!res[1].round_amounts.contains_key(&two));
-    assert!(res[1].round_amounts[&three] == 23_u128);
+    assert!(!res[1].round_amounts.contains_key(&(one, None)));
+    assert!(!res[1].round_amounts.contains_key(&(two, None)));
+    assert!(res[1].round_amounts[&(three, None)] == 23_u128);
 }
 
 #[test]
@@ -140,26 +147,32 @@ fn test_cumulative_three_rounds() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop2.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "23".to_string(),
+        ..Default::default()
     });
     drop3.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "33".to_string(),
+        ..Default::default()
     });
     drop3.push(JSONAllocation {
         address: "0x4".to_string(),
         amount: "50".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -175,7 +188,7 @@ fn test_cumulative_three_rounds() {
         round: 3u8,
         amounts: drop3,
     });
-    let res = map_cumulative_amounts(round_data);
+    let res = map_cumulative_amounts(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 3);
 
@@ -183,18 +196,18 @@ fn test_cumulative_three_rounds() {
     assert!(res[2].round_amounts.len() == 2);
     assert!(res[2].round == 3_u8);
 
-    assert!(res[2].round_amounts[&two] == 33_u128);
-    assert!(res[2].round_amounts[&four] == 50_u128);
+    assert!(res[2].round_amounts[&(two, None)] == 33_u128);
+    assert!(res[2].round_amounts[&(four, None)] == 50_u128);
 
-    assert!(res[2].cumulative_amounts[&one] == 5_u128);
-    assert!(res[2].cumulative_amounts[&two] == 39_u128);
-    assert!(res[2].cumulative_amounts[&three] == 30_u128);
-    assert!(res[2].cumulative_amounts[&four] == 50_u128);
+    assert!(res[2].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[2].cumulative_amounts[&(two, None)] == 39_u128);
+    assert!(res[2].cumulative_amounts[&(three, None)] == 30_u128);
+    assert!(res[2].cumulative_amounts[&(four, None)] == 50_u128);
 
-    assert!(!res[2].round_amounts.contains_key(&one));
-    assert!(res[2].round_amounts[&two] == 33_u128);
-    assert!(!res[2].round_amounts.contains_key(&three));
-    assert!(res[2].round_amounts[&four] == 50_u128);
+    assert!(!res[2].round_amounts.contains_key(&(one, None)));
+    assert!(res[2].round_amounts[&(two, None)] == 33_u128);
+    assert!(!res[2].round_amounts.contains_key(&(three, None)));
+    assert!(res[2].round_amounts[&(four, None)] == 50_u128);
 }
 
 #[test]
@@ -208,18 +221,22 @@ fn test_skip_round() {
     drop1.push(JSONAllocation {
         address: "0x1".to_string(),
         amount: "5".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "6".to_string(),
+        ..Default::default()
     });
     drop1.push(JSONAllocation {
         address: "0x3".to_string(),
         amount: "7".to_string(),
+        ..Default::default()
     });
     drop3.push(JSONAllocation {
         address: "0x2".to_string(),
         amount: "33".to_string(),
+        ..Default::default()
     });
 
     let mut round_data: Vec<RoundAmounts> = Vec::new();
@@ -231,7 +248,7 @@ fn test_skip_round() {
         round: 3u8,
         amounts: drop3,
     });
-    let res = map_cumulative_amounts(round_data);
+    let res = map_cumulative_amounts(round_data, &CampaignConfig::default());
 
     assert!(res.len() == 2);
 
@@ -243,19 +260,19 @@ fn test_skip_round() {
     assert!(res[1].round_amounts.len() == 1);
     assert!(res[1].round == 3_u8);
 
-    assert!(res[0].cumulative_amounts[&one] == 5_u128);
-    assert!(res[0].cumulative_amounts[&two] == 6_u128);
-    assert!(res[0].cumulative_amounts[&three] == 7_u128);
+    assert!(res[0].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].cumulative_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].cumulative_amounts[&(three, None)] == 7_u128);
 
-    assert!(res[0].round_amounts[&one] == 5_u128);
-    assert!(res[0].round_amounts[&two] == 6_u128);
-    assert!(res[0].round_amounts[&three] == 7_u128);
+    assert!(res[0].round_amounts[&(one, None)] == 5_u128);
+    assert!(res[0].round_amounts[&(two, None)] == 6_u128);
+    assert!(res[0].round_amounts[&(three, None)] == 7_u128);
 
-    assert!(res[1].cumulative_amounts[&one] == 5_u128);
-    assert!(res[1].cumulative_amounts[&two] == 39_u128);
-    assert!(res[1].cumulative_amounts[&three] == 7_u128);
+    assert!(res[1].cumulative_amounts[&(one, None)] == 5_u128);
+    assert!(res[1].cumulative_amounts[&(two, None)] == 39_u128);
+    assert!(res[1].cumulative_amounts[&(three, None)] == 7_u128);
 
-    assert!(!res[1].round_amounts.contains_key(&one));
-    assert!(res[1].round_amounts[&two] == 33_u128);
-    assert!(!res[1].round_amounts.contains_key(&three));
+    assert!(!res[1].round_amounts.contains_key(&(one, None)));
+    assert!(res[1].round_amounts[&(two, None)] == 33_u128);
+    assert!(!res[1].round_amounts.contains_key(&(three, None)));
 }
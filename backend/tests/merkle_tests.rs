@@ -1,7 +1,10 @@
 use starknet_crypto::{pedersen_hash, poseidon_hash, FieldElement};
 use std::str::FromStr;
 
-use defispring::api::structs::{CairoCalldata, CumulativeAllocation, MerkleTree};
+use defispring::api::{
+    errors::ErrorKind,
+    structs::{CairoCalldata, CumulativeAllocation, MerkleTree},
+};
 
 // mockup of a function that will be used in the SC
 // root will not be passed as an argument but stored in the SC
@@ -54,18 +57,21 @@ fn valid_addresses() {
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x1").unwrap(),
         cumulative_amount: 1,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x2").unwrap(),
         cumulative_amount: 2,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x3").unwrap(),
         cumulative_amount: 3,
+        ..Default::default()
     });
 
     let mt = MerkleTree::new(allocations.clone());
-    let root = mt.root.value.clone();
+    let root = mt.root;
 
     for alloc in allocations.iter() {
         let str = FieldElement::to_string(&alloc.address);
@@ -94,16 +100,23 @@ fn invalid_calldata_address() {
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x1").unwrap(),
         cumulative_amount: 1,
+        ..Default::default()
     });
 
     let mt = MerkleTree::new(allocations.clone());
 
     assert_eq!(
-        mt.address_calldata("blah").unwrap_err(),
+        mt.address_calldata("blah").unwrap_err().kind,
+        ErrorKind::InvalidAddress
+    );
+    assert_eq!(
+        mt.address_calldata("0xq").unwrap_err().message,
+        "invalid character"
+    );
+    assert_eq!(
+        mt.address_calldata("1q").unwrap_err().message,
         "invalid character"
     );
-    assert_eq!(mt.address_calldata("0xq").unwrap_err(), "invalid character");
-    assert_eq!(mt.address_calldata("1q").unwrap_err(), "invalid character");
 }
 
 /// Tests that modifying the calldata fails
@@ -113,20 +126,23 @@ fn fail_with_calldata_tempering() {
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x1").unwrap(),
         cumulative_amount: 1,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x2").unwrap(),
         cumulative_amount: 2,
+        ..Default::default()
     });
     allocations.push(CumulativeAllocation {
         address: FieldElement::from_str("0x3").unwrap(),
         cumulative_amount: 3,
+        ..Default::default()
     });
 
     let mt = MerkleTree::new(allocations.clone());
 
     let hacked_amount = 10_u128;
-    let root = mt.root.value.clone();
+    let root = mt.root;
 
     for alloc in allocations.iter() {
         let str = FieldElement::to_string(&alloc.address);
@@ -140,3 +156,90 @@ fn fail_with_calldata_tempering() {
         ));
     }
 }
+
+/// Tests that the root of a bigger tree doesn't change, no matter how the tree is built (e.g. in parallel)
+#[test]
+fn stable_root_for_big_tree() {
+    let allocations: Vec<CumulativeAllocation> = (1..=1001_u64)
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i * 7919),
+            cumulative_amount: (i * 1000) as u128,
+            ..Default::default()
+        })
+        .collect();
+
+    let mt = MerkleTree::new(allocations);
+
+    assert_eq!(
+        format!("{:#x}", mt.root),
+        "0x74def7988fcfe8b47c284e175186a80d08bf676aa0cfad19dff426578017314"
+    );
+}
+
+/// Tests that every proof verifies for trees of various sizes, including ones that need padding
+#[test]
+fn valid_proofs_for_all_sizes() {
+    for size in 1..=9_u64 {
+        let allocations: Vec<CumulativeAllocation> = (1..=size)
+            .map(|i| CumulativeAllocation {
+                address: FieldElement::from(i),
+                cumulative_amount: i as u128,
+                ..Default::default()
+            })
+            .collect();
+
+        let mt = MerkleTree::new(allocations.clone());
+
+        for alloc in allocations.iter() {
+            let str = FieldElement::to_string(&alloc.address);
+            let calldata = mt.address_calldata(&str).expect("Failed getting calldata");
+            assert!(cairo_root_generating(alloc.address, calldata, mt.root));
+        }
+    }
+}
+
+/// Tests that the address index finds every leaf, including the leaves of an address with several tokens
+#[test]
+fn address_index_lookups() {
+    let mut allocations: Vec<CumulativeAllocation> = (1..=100_u64)
+        .rev()
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i * 3),
+            cumulative_amount: i as u128,
+            ..Default::default()
+        })
+        .collect();
+    for token in [2_u64, 1] {
+        allocations.push(CumulativeAllocation {
+            address: FieldElement::from(30_u64),
+            cumulative_amount: 1000 + token as u128,
+            token: Some(FieldElement::from(token)),
+            ..Default::default()
+        });
+    }
+
+    let mt = MerkleTree::new(allocations);
+
+    assert_eq!(mt.address_index.len(), 100);
+    for (address, range) in mt.address_index.iter() {
+        assert!(mt.allocations[range.clone()]
+            .iter()
+            .all(|a| a.address == *address));
+    }
+    // The two token leaves of the address 30 come before 33
+    assert_eq!(mt.find_leaf(&FieldElement::from(33_u64), None), Some(12));
+    assert_eq!(mt.find_leaf(&FieldElement::from(31_u64), None), None);
+
+    // The address 30 has a leaf without a token and one for each token
+    let address = FieldElement::from(30_u64);
+    let index = mt.find_leaf(&address, None).unwrap();
+    assert_eq!(mt.allocations[index].token, None);
+    let index = mt
+        .find_leaf(&address, Some(&FieldElement::from(2_u64)))
+        .unwrap();
+    assert_eq!(mt.allocations[index].cumulative_amount, 1002);
+    assert_eq!(
+        mt.find_leaf(&address, Some(&FieldElement::from(3_u64))),
+        None
+    );
+}
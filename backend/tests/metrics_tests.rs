@@ -0,0 +1,52 @@
+/// This file tests the metrics collected for the "/metrics" endpoint
+use std::str::FromStr;
+
+use actix_web::{
+    test::{call_and_read_body, call_service, init_service, TestRequest},
+    App,
+};
+use defispring::api::{
+    endpoints::{get_metrics, get_root},
+    metrics::{gather_metrics, RequestMetrics},
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use starknet_crypto::FieldElement;
+
+/// Tests that building a tree is recorded
+#[test]
+fn tree_build_is_recorded() {
+    let allocations = vec![CumulativeAllocation {
+        address: FieldElement::from_str("0x1").unwrap(),
+        cumulative_amount: 1,
+        ..Default::default()
+    }];
+
+    MerkleTree::new(allocations);
+
+    assert!(gather_metrics().contains("merkle_tree_build_duration_seconds_count"));
+}
+
+/// Tests that requests and errors are counted per endpoint and kind
+#[actix_web::test]
+async fn requests_are_counted() {
+    let app = init_service(
+        App::new()
+            .wrap(RequestMetrics)
+            .service(get_root)
+            .service(get_metrics),
+    )
+    .await;
+
+    // No data has been loaded, so this fails
+    let req = TestRequest::get().uri("/get_root").to_request();
+    let res = call_service(&app, req).await;
+    assert!(res.status().is_client_error());
+
+    let req = TestRequest::get().uri("/metrics").to_request();
+    let body = call_and_read_body(&app, req).await;
+    let metrics = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(metrics.contains(r#"http_requests_total{endpoint="/get_root",status="400"} 1"#));
+    assert!(metrics.contains(r#"api_errors_total{kind="round_not_found"} 1"#));
+    assert!(metrics.contains(r#"http_request_duration_seconds_count{endpoint="/get_root"} 1"#));
+}
@@ -0,0 +1,154 @@
+/// This file tests allocations in several tokens in the same round
+use defispring::api::{
+    merkle_tree::verify_multiproof,
+    structs::{CampaignConfig, JSONAllocation, RoundTreeData, TokenTotals},
+};
+use serde_json::from_str;
+use starknet_crypto::{poseidon_hash_many, FieldElement};
+use std::str::FromStr;
+
+mod common;
+use common::rounds_of;
+
+const STRK: &str = "0x4718";
+const PARTNER: &str = "0x5555";
+
+fn token_rounds() -> Vec<RoundTreeData> {
+    let round1: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "5", "token": "0x4718"},
+            {"address": "0x1", "amount": "7", "token": "0x5555"},
+            {"address": "0x2", "amount": "6", "token": "0x4718"}
+        ]"#,
+    )
+    .unwrap();
+    let round2: Vec<JSONAllocation> = from_str(
+        r#"[
+            {"address": "0x1", "amount": "1", "token": "0x4718"},
+            {"address": "0x3", "amount": "2"}
+        ]"#,
+    )
+    .unwrap();
+
+    rounds_of(vec![round1, round2], &CampaignConfig::default())
+}
+
+/// Tests that each (address, token) pair is accumulated into its own leaf
+#[test]
+fn amounts_per_token() {
+    let res = token_rounds();
+    let one = FieldElement::from_str("0x1").unwrap();
+    let strk = FieldElement::from_str(STRK).unwrap();
+    let partner = FieldElement::from_str(PARTNER).unwrap();
+
+    assert_eq!(res[0].tree.allocations.len(), 3);
+    assert_eq!(res[1].tree.allocations.len(), 4);
+
+    assert_eq!(res[1].address_token_amount(one, Some(strk)).unwrap(), 6);
+    assert_eq!(res[1].address_token_amount(one, Some(partner)).unwrap(), 7);
+    // Without a token it's the allocation without a token
+    assert_eq!(
+        res[1]
+            .address_amount(FieldElement::from_str("0x3").unwrap())
+            .unwrap(),
+        2
+    );
+    // Address 1 only has allocations in tokens
+    assert_eq!(res[1].address_amount(one).unwrap(), 0);
+    // Unknown token results in zero
+    assert_eq!(
+        res[1]
+            .address_token_amount(one, Some(FieldElement::from_str("0x9").unwrap()))
+            .unwrap(),
+        0
+    );
+}
+
+/// Tests the totals of each token. Rows without a token are only in the overall totals
+#[test]
+fn token_totals() {
+    let res = token_rounds();
+    let strk = FieldElement::from_str(STRK).unwrap();
+    let partner = FieldElement::from_str(PARTNER).unwrap();
+
+    assert_eq!(
+        res[0].token_totals,
+        vec![
+            TokenTotals {
+                token: strk,
+                accumulated_total_amount: 11,
+                round_total_amount: 11,
+            },
+            TokenTotals {
+                token: partner,
+                accumulated_total_amount: 7,
+                round_total_amount: 7,
+            },
+        ]
+    );
+    assert_eq!(
+        res[1].token_totals,
+        vec![
+            TokenTotals {
+                token: strk,
+                accumulated_total_amount: 12,
+                round_total_amount: 1,
+            },
+            TokenTotals {
+                token: partner,
+                accumulated_total_amount: 7,
+                round_total_amount: 0,
+            },
+        ]
+    );
+    // Only the row without a token
+    assert_eq!(res[1].accumulated_total_amount, 2);
+    assert_eq!(res[1].round_total_amount, 2);
+}
+
+/// Tests that the token is hashed into the leaf and given in the calldata
+#[test]
+fn token_calldata() {
+    let res = token_rounds();
+    let tree = &res[1].tree;
+
+    let calldata = tree.address_token_calldata("0x1", Some(PARTNER)).unwrap();
+    assert_eq!(calldata.amount, "0x7");
+    assert_eq!(
+        calldata.token.as_deref(),
+        Some("0x0000000000000000000000000000000000000000000000000000000000005555")
+    );
+
+    let leaf = poseidon_hash_many(&[
+        FieldElement::from(1_u64),
+        FieldElement::from(7_u64),
+        FieldElement::ONE,
+        FieldElement::from_str(PARTNER).unwrap(),
+        FieldElement::ZERO,
+    ]);
+    let index = tree
+        .find_leaf(
+            &FieldElement::from(1_u64),
+            Some(&FieldElement::from_str(PARTNER).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(tree.levels[0][index], leaf);
+
+    assert_eq!(
+        tree.address_calldata("0x1").unwrap_err().message,
+        "Address not found in tree"
+    );
+    assert!(tree.address_calldata("0x3").unwrap().token.is_none());
+
+    // A multiproof covers all tokens of an address
+    let multiproof = tree
+        .multiproof_calldata(&["0x1".to_string(), "0x3".to_string()])
+        .unwrap();
+    assert_eq!(multiproof.addresses.len(), 3);
+    assert!(verify_multiproof(&tree.hasher, &tree.root, &multiproof));
+
+    // Swapping the tokens of the leaves breaks the proof
+    let mut hacked = multiproof.clone();
+    hacked.tokens.swap(0, 1);
+    assert!(!verify_multiproof(&tree.hasher, &tree.root, &hacked));
+}
@@ -0,0 +1,120 @@
+/// This file tests generating and verifying multiproofs
+use defispring::api::{
+    errors::{ApiError, ErrorKind},
+    merkle_tree::verify_multiproof,
+    structs::{CumulativeAllocation, MerkleTree},
+};
+use starknet_crypto::FieldElement;
+
+fn tree_of_size(size: u64) -> MerkleTree {
+    let allocations: Vec<CumulativeAllocation> = (1..=size)
+        .map(|i| CumulativeAllocation {
+            address: FieldElement::from(i),
+            cumulative_amount: (i * 10) as u128,
+            ..Default::default()
+        })
+        .collect();
+    MerkleTree::new(allocations)
+}
+
+/// Tests that multiproofs verify for all kinds of address subsets and tree sizes
+#[test]
+fn valid_multiproofs() {
+    for size in 1..=9_u64 {
+        let mt = tree_of_size(size);
+
+        // All contiguous subsets
+        for start in 1..=size {
+            for end in start..=size {
+                let addresses: Vec<String> = (start..=end).map(|i| format!("{:#x}", i)).collect();
+                let calldata = mt
+                    .multiproof_calldata(&addresses)
+                    .expect("Failed getting multiproof");
+                assert_eq!(calldata.addresses.len(), addresses.len());
+                assert!(verify_multiproof(&mt.hasher, &mt.root, &calldata));
+            }
+        }
+
+        // Every other address
+        let addresses: Vec<String> = (1..=size).step_by(2).map(|i| i.to_string()).collect();
+        let calldata = mt.multiproof_calldata(&addresses).unwrap();
+        assert!(verify_multiproof(&mt.hasher, &mt.root, &calldata));
+    }
+}
+
+/// Tests that a multiproof for one address matches the single proof
+#[test]
+fn single_leaf_multiproof() {
+    let mt = tree_of_size(5);
+
+    let single = mt.address_calldata("0x3").unwrap();
+    let multi = mt.multiproof_calldata(&["0x3".to_string()]).unwrap();
+
+    assert_eq!(single.proof, multi.proof);
+    assert_eq!(vec![single.amount], multi.amounts);
+    assert!(multi.proof_flags.iter().all(|f| !f));
+}
+
+/// Tests that duplicate and unordered addresses are handled
+#[test]
+fn unordered_and_duplicate_addresses() {
+    let mt = tree_of_size(7);
+
+    let addresses: Vec<String> = vec!["0x6", "0x2", "0x6", "4"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let calldata = mt.multiproof_calldata(&addresses).unwrap();
+
+    // Addresses are returned in the canonical format
+    let expected: Vec<String> = [2_u64, 4, 6]
+        .iter()
+        .map(|i| format!("0x{:064x}", i))
+        .collect();
+    assert_eq!(calldata.addresses, expected);
+    assert!(verify_multiproof(&mt.hasher, &mt.root, &calldata));
+}
+
+/// Tests that a tampered multiproof fails
+#[test]
+fn fail_with_multiproof_tampering() {
+    let mt = tree_of_size(8);
+    let addresses: Vec<String> = vec!["0x1".to_string(), "0x5".to_string()];
+    let calldata = mt.multiproof_calldata(&addresses).unwrap();
+
+    let mut hacked = calldata.clone();
+    hacked.amounts[1] = "0x1000".to_string();
+    assert!(!verify_multiproof(&mt.hasher, &mt.root, &hacked));
+
+    let mut hacked = calldata.clone();
+    hacked.proof.pop();
+    assert!(!verify_multiproof(&mt.hasher, &mt.root, &hacked));
+
+    let mut hacked = calldata.clone();
+    hacked.proof_flags[0] = !hacked.proof_flags[0];
+    assert!(!verify_multiproof(&mt.hasher, &mt.root, &hacked));
+
+    let mut hacked = calldata.clone();
+    hacked.addresses.pop();
+    assert!(!verify_multiproof(&mt.hasher, &mt.root, &hacked));
+}
+
+/// Tests that unknown addresses fail
+#[test]
+fn fail_with_unknown_address() {
+    let mt = tree_of_size(3);
+
+    assert_eq!(
+        mt.multiproof_calldata(&["0x1".to_string(), "0x9".to_string()])
+            .unwrap_err(),
+        ApiError::new(ErrorKind::AddressNotFound, "Address not found in tree")
+    );
+    assert_eq!(
+        mt.multiproof_calldata(&["0xq".to_string()]).unwrap_err(),
+        ApiError::new(ErrorKind::InvalidAddress, "invalid character")
+    );
+    assert_eq!(
+        mt.multiproof_calldata(&[]).unwrap_err(),
+        ApiError::new(ErrorKind::Other, "No addresses given")
+    );
+}
@@ -0,0 +1,129 @@
+/// This file checks invariants of the trees and the accumulation on generated inputs
+use defispring::api::{
+    distributor::Distributor,
+    math::felt_to_u128,
+    merkle_tree::felt_to_b16,
+    processor::transform_allocations_to_cumulative_rounds,
+    structs::{CampaignConfig, CumulativeAllocation, JSONAllocation, MerkleTree, RoundAmounts},
+};
+use proptest::prelude::*;
+use starknet_crypto::FieldElement;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// Allocations with unique addresses
+fn allocations(max_len: usize) -> impl Strategy<Value = Vec<CumulativeAllocation>> {
+    prop::collection::btree_map(1..u64::MAX, 0..u128::MAX / 2, 1..max_len).prop_map(|amounts| {
+        amounts
+            .into_iter()
+            .map(|(address, amount)| CumulativeAllocation {
+                address: FieldElement::from(address),
+                cumulative_amount: amount,
+                token: None,
+                extra_fields: vec![],
+            })
+            .collect()
+    })
+}
+
+/// Rounds 1..n with rows of a small set of addresses, so the rounds overlap.
+/// An address can have several rows in a round
+fn rounds() -> impl Strategy<Value = Vec<RoundAmounts>> {
+    let row = (1..20_u64, 0..1_000_000_u128).prop_map(|(address, amount)| JSONAllocation {
+        address: format!("{:#x}", address),
+        amount: amount.to_string(),
+        ..Default::default()
+    });
+    prop::collection::vec(prop::collection::vec(row, 1..15), 1..6).prop_map(|rounds| {
+        rounds
+            .into_iter()
+            .enumerate()
+            .map(|(i, amounts)| RoundAmounts {
+                round: i as u8 + 1,
+                amounts,
+            })
+            .collect()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    /// Every leaf's proof verifies against the root with the claim logic of the contract
+    #[test]
+    fn every_proof_verifies(allocations in allocations(40)) {
+        let tree = MerkleTree::new(allocations.clone());
+        let distributor = Distributor::new(FieldElement::ZERO);
+
+        for allocation in allocations.iter() {
+            let calldata = tree.address_calldata(&felt_to_b16(&allocation.address)).unwrap();
+            let amount = felt_to_u128(&FieldElement::from_str(&calldata.amount).unwrap()).unwrap();
+            let proof: Vec<FieldElement> = calldata
+                .proof
+                .iter()
+                .map(|p| FieldElement::from_str(p).unwrap())
+                .collect();
+            prop_assert_eq!(amount, allocation.cumulative_amount);
+            prop_assert_eq!(distributor.get_root_for(allocation.address, amount, &proof), tree.root);
+        }
+    }
+
+    /// The root doesn't depend on the order of the input
+    #[test]
+    fn root_independent_of_order(
+        (allocations, shuffled) in allocations(40)
+            .prop_flat_map(|a| (Just(a.clone()), Just(a).prop_shuffle()))
+    ) {
+        prop_assert_eq!(MerkleTree::new(allocations).root, MerkleTree::new(shuffled).root);
+    }
+
+    /// The order of the rows of the input files doesn't change the roots
+    #[test]
+    fn rounds_independent_of_row_order(
+        (rounds, shuffled) in rounds().prop_flat_map(|rounds| {
+            let shuffled: Vec<_> = rounds
+                .iter()
+                .map(|r| {
+                    let round = r.round;
+                    Just(r.amounts.clone())
+                        .prop_shuffle()
+                        .prop_map(move |amounts| RoundAmounts { round, amounts })
+                })
+                .collect();
+            (Just(rounds), shuffled)
+        })
+    ) {
+        let config = CampaignConfig::default();
+        let roots = |rounds| -> Vec<FieldElement> {
+            transform_allocations_to_cumulative_rounds(rounds, &config)
+                .iter()
+                .map(|r| r.tree.root)
+                .collect()
+        };
+        prop_assert_eq!(roots(rounds), roots(shuffled));
+    }
+
+    /// Cumulative amounts never decrease from a round to the next, and the round totals add up to the accumulated total
+    #[test]
+    fn cumulative_amounts_and_totals(rounds in rounds()) {
+        let res = transform_allocations_to_cumulative_rounds(rounds, &CampaignConfig::default());
+
+        let mut previous: BTreeMap<FieldElement, u128> = BTreeMap::new();
+        let mut round_totals = 0_u128;
+        for round in res.iter() {
+            let current: BTreeMap<FieldElement, u128> = round
+                .tree
+                .allocations
+                .iter()
+                .map(|a| (a.address, a.cumulative_amount))
+                .collect();
+            for (address, amount) in previous.iter() {
+                prop_assert!(current.get(address).copied().unwrap_or(0) >= *amount);
+            }
+
+            round_totals += round.round_total_amount;
+            prop_assert_eq!(round_totals, round.accumulated_total_amount);
+            prop_assert_eq!(current.values().sum::<u128>(), round.accumulated_total_amount);
+            previous = current;
+        }
+    }
+}
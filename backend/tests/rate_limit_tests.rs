@@ -0,0 +1,251 @@
+/// This file tests the rate limits and the request size limits
+use actix_web::{
+    http::{header, StatusCode},
+    test as actix_test, web, App, HttpResponse,
+};
+use defispring::api::{
+    metrics::gather_metrics,
+    rate_limit::{
+        read_rate_limits, BucketConfig, EndpointLimits, RateLimitConfig, RateLimitState,
+        RateLimiter, Rejection,
+    },
+};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+fn ip(value: &str) -> Option<IpAddr> {
+    Some(value.parse().unwrap())
+}
+
+fn bucket(rate: f64, burst: f64) -> Option<BucketConfig> {
+    Some(BucketConfig { rate, burst })
+}
+
+/// Tests that each IP gets its burst and then one request per token added
+#[test]
+fn per_ip_buckets() {
+    let state = RateLimitState::new(RateLimitConfig {
+        per_ip: bucket(2.0, 3.0),
+        global: None,
+        ..Default::default()
+    });
+    let now = Instant::now();
+
+    for _ in 0..3 {
+        assert_eq!(state.check("/get_root", ip("1.2.3.4"), "", now), Ok(()));
+    }
+    assert_eq!(
+        state.check("/get_root", ip("1.2.3.4"), "", now),
+        Err(Rejection::PerIpLimit(Duration::from_millis(500)))
+    );
+
+    // Other IPs and endpoints have their own buckets
+    assert_eq!(state.check("/get_root", ip("1.2.3.5"), "", now), Ok(()));
+    assert_eq!(state.check("/get_calldata", ip("1.2.3.4"), "", now), Ok(()));
+
+    // A token is added every 0.5 seconds
+    let later = now + Duration::from_millis(500);
+    assert_eq!(state.check("/get_root", ip("1.2.3.4"), "", later), Ok(()));
+    assert!(state.check("/get_root", ip("1.2.3.4"), "", later).is_err());
+}
+
+/// Tests the global limit, the limits of an endpoint and the allowlist
+#[test]
+fn global_and_endpoint_limits() {
+    let state = RateLimitState::new(RateLimitConfig {
+        per_ip: bucket(100.0, 100.0),
+        global: bucket(1.0, 2.0),
+        endpoints: HashMap::from([(
+            "/get_multiproof_calldata".to_string(),
+            EndpointLimits {
+                per_ip: bucket(1.0, 1.0),
+                global: None,
+            },
+        )]),
+        allowlist: vec![ip("10.0.0.1").unwrap()],
+        ..Default::default()
+    });
+    let now = Instant::now();
+
+    assert!(state.check("/get_root", ip("1.1.1.1"), "", now).is_ok());
+    assert!(state.check("/get_root", ip("2.2.2.2"), "", now).is_ok());
+    assert!(matches!(
+        state.check("/get_root", ip("3.3.3.3"), "", now),
+        Err(Rejection::GlobalLimit(_))
+    ));
+    // Trusted callers aren't limited
+    assert!(state.check("/get_root", ip("10.0.0.1"), "", now).is_ok());
+
+    // The endpoint has its own IP limit and the default global limit
+    assert!(state
+        .check("/get_multiproof_calldata", ip("1.1.1.1"), "", now)
+        .is_ok());
+    assert!(matches!(
+        state.check("/get_multiproof_calldata", ip("1.1.1.1"), "", now),
+        Err(Rejection::PerIpLimit(_))
+    ));
+}
+
+/// Tests that a request rejected by the global limit doesn't use up the token of its IP
+#[test]
+fn rejected_requests_take_no_tokens() {
+    let state = RateLimitState::new(RateLimitConfig {
+        per_ip: bucket(0.1, 1.0),
+        global: bucket(1.0, 1.0),
+        ..Default::default()
+    });
+    let now = Instant::now();
+
+    assert!(state.check("/get_root", ip("1.1.1.1"), "", now).is_ok());
+    assert!(matches!(
+        state.check("/get_root", ip("2.2.2.2"), "", now),
+        Err(Rejection::GlobalLimit(_))
+    ));
+
+    // Once the global bucket has a token again, the rejected IP still has its own, which would take 10 seconds to come back
+    let later = now + Duration::from_secs(1);
+    assert!(state.check("/get_root", ip("2.2.2.2"), "", later).is_ok());
+    assert!(matches!(
+        state.check("/get_root", ip("2.2.2.2"), "", later),
+        Err(Rejection::PerIpLimit(_))
+    ));
+}
+
+/// Tests the limits on the query string and the number of addresses
+#[test]
+fn request_size_limits() {
+    let state = RateLimitState::new(RateLimitConfig {
+        max_query_length: 100,
+        max_batch_size: 3,
+        allowlist: vec![ip("10.0.0.1").unwrap()],
+        ..Default::default()
+    });
+    let now = Instant::now();
+    let caller = ip("1.1.1.1");
+
+    assert_eq!(
+        state.check("/get_root", caller, &"a".repeat(101), now),
+        Err(Rejection::QueryTooLong)
+    );
+    assert!(state
+        .check(
+            "/get_multiproof_calldata",
+            caller,
+            "addresses=0x1,0x2,0x3",
+            now
+        )
+        .is_ok());
+    assert_eq!(
+        state.check(
+            "/get_multiproof_calldata",
+            caller,
+            "addresses=0x1,0x2,0x3,0x4",
+            now
+        ),
+        Err(Rejection::BatchTooLarge(3))
+    );
+    // Empty entries and encoded commas count like in the endpoint
+    assert!(state
+        .check(
+            "/get_multiproof_calldata",
+            caller,
+            "addresses=0x1,,0x2%2C0x3,",
+            now
+        )
+        .is_ok());
+    assert!(state
+        .check(
+            "/get_multiproof_calldata",
+            ip("10.0.0.1"),
+            "addresses=0x1,0x2,0x3,0x4",
+            now
+        )
+        .is_ok());
+}
+
+/// Tests reading the limits from a file
+#[test]
+fn limits_file() {
+    let config = read_rate_limits("./tests/test_rate_limits/rate_limits.json");
+    assert_eq!(config.per_ip, bucket(5.0, 10.0));
+    // Settings that aren't given keep their defaults
+    assert_eq!(config.global, RateLimitConfig::default().global);
+    assert_eq!(config.max_query_length, 4096);
+    assert_eq!(config.max_batch_size, 50);
+    assert_eq!(
+        config.allowlist,
+        vec![ip("10.0.0.5").unwrap(), ip("::1").unwrap()]
+    );
+    assert!(config.trust_forwarded_for);
+    assert_eq!(
+        config.endpoints["/get_multiproof_calldata"].per_ip,
+        bucket(0.5, 2.0)
+    );
+
+    let missing = read_rate_limits("./tests/test_rate_limits/missing.json");
+    assert_eq!(missing.per_ip, RateLimitConfig::default().per_ip);
+}
+
+/// Tests the responses of the middleware and the client IP behind a proxy
+#[actix_web::test]
+async fn middleware_responses() {
+    let limiter = |trust_forwarded_for: bool| {
+        RateLimiter::new(RateLimitConfig {
+            per_ip: bucket(0.1, 1.0),
+            max_query_length: 50,
+            trust_forwarded_for,
+            ..Default::default()
+        })
+    };
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(limiter(false))
+            .route("/limited", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let get = |uri: &str, peer: &str| {
+        actix_test::TestRequest::get()
+            .uri(uri)
+            .peer_addr(peer.parse().unwrap())
+            .to_request()
+    };
+
+    let res = actix_test::call_service(&app, get("/limited", "1.1.1.1:1000")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = actix_test::call_service(&app, get("/limited", "1.1.1.1:1001")).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "10");
+    assert_eq!(actix_test::read_body(res).await, "\"Too many requests\"");
+    let res = actix_test::call_service(&app, get("/limited", "2.2.2.2:1000")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let uri = format!("/limited?address={}", "1".repeat(50));
+    let res = actix_test::call_service(&app, get(&uri, "3.3.3.3:1000")).await;
+    assert_eq!(res.status(), StatusCode::URI_TOO_LONG);
+    assert!(gather_metrics()
+        .contains("rejected_requests_total{endpoint=\"/limited\",reason=\"per_ip_limit\"}"));
+
+    // Behind a trusted proxy the forwarded IP is limited, not the proxy
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(limiter(true))
+            .route("/limited", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let forwarded = |client: &str| {
+        actix_test::TestRequest::get()
+            .uri("/limited")
+            .peer_addr("10.0.0.1:1000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", client))
+            .to_request()
+    };
+    let res = actix_test::call_service(&app, forwarded("1.1.1.1")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = actix_test::call_service(&app, forwarded("2.2.2.2")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = actix_test::call_service(&app, forwarded("1.1.1.1")).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
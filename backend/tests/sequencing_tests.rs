@@ -0,0 +1,99 @@
+/// This file tests the mapping of rounds to the root slots of the contract
+use defispring::api::{
+    errors::{ApiError, ErrorKind},
+    sequencing::{missing_rounds, publication_plan, select_round},
+    structs::{CampaignConfig, PublicationStatus, RoundState, RoundTreeData},
+};
+use std::collections::BTreeMap;
+
+mod common;
+use common::numbered_rounds;
+
+fn slots(rounds: &[RoundTreeData]) -> Vec<(u8, Option<u64>)> {
+    rounds.iter().map(|r| (r.round, r.slot)).collect()
+}
+
+/// Tests that consecutive rounds get consecutive slots and rounds after a gap get none
+#[test]
+fn slots_with_gaps() {
+    let res = numbered_rounds(&[1, 2, 3], &CampaignConfig::default());
+    assert_eq!(slots(&res), vec![(1, Some(0)), (2, Some(1)), (3, Some(2))]);
+
+    let res = numbered_rounds(&[2, 3, 5, 6], &CampaignConfig::default());
+    assert_eq!(
+        slots(&res),
+        vec![(2, Some(0)), (3, Some(1)), (5, None), (6, None)]
+    );
+    assert_eq!(missing_rounds(&res, None), vec![4]);
+}
+
+/// Tests that the first round of the campaign goes to slot 0 even if its data is missing
+#[test]
+fn first_round_of_campaign() {
+    let config = CampaignConfig {
+        first_round: Some(1),
+        ..Default::default()
+    };
+    let res = numbered_rounds(&[2, 3], &config);
+    assert_eq!(slots(&res), vec![(2, None), (3, None)]);
+    assert_eq!(missing_rounds(&res, config.first_round), vec![1]);
+
+    let res = numbered_rounds(&[1, 2], &config);
+    assert_eq!(slots(&res), vec![(1, Some(0)), (2, Some(1))]);
+}
+
+/// Tests that rounds with missing predecessors aren't served
+#[test]
+fn refuses_unsequenced_rounds() {
+    let res = numbered_rounds(&[1, 2, 4], &CampaignConfig::default());
+
+    assert_eq!(select_round(&res, Some(2)).unwrap().round, 2);
+    assert_eq!(
+        select_round(&res, Some(4)).unwrap_err(),
+        ApiError::new(
+            ErrorKind::RoundNotSequenced,
+            "Round can't be served before all of its predecessors"
+        )
+    );
+    assert_eq!(
+        select_round(&res, Some(3)).unwrap_err().kind,
+        ErrorKind::RoundNotFound
+    );
+    // The latest round is the latest one with a slot
+    assert_eq!(select_round(&res, None).unwrap().round, 2);
+    assert!(select_round::<RoundTreeData>(&[], None).is_err());
+}
+
+/// Tests the statuses of the plan against the roots already on the contract
+#[test]
+fn plan_statuses() {
+    let res = numbered_rounds(&[1, 2, 3, 5], &CampaignConfig::default());
+    let mut config = CampaignConfig {
+        published_roots: vec![res[0].tree.root, res[0].tree.root],
+        ..Default::default()
+    };
+
+    // Drafts aren't listed. Round 1 is published, so it doesn't need an approval
+    let plan = publication_plan(&res, &config);
+    assert_eq!(plan.steps.len(), 1);
+    assert_eq!(plan.missing_rounds, vec![4]);
+
+    config.round_states = BTreeMap::from([(2, RoundState::Approved), (3, RoundState::Approved)]);
+    let plan = publication_plan(&res, &config);
+    assert_eq!(plan.next_free_slot, 2);
+    assert_eq!(plan.missing_rounds, vec![4]);
+
+    let steps: Vec<(u8, u64, PublicationStatus)> = plan
+        .steps
+        .iter()
+        .map(|s| (s.round, s.slot, s.status))
+        .collect();
+    assert_eq!(
+        steps,
+        vec![
+            (1, 0, PublicationStatus::Published),
+            (2, 1, PublicationStatus::Mismatch),
+            (3, 2, PublicationStatus::Pending),
+        ]
+    );
+}
@@ -0,0 +1,125 @@
+/// This file tests vesting schedules
+use defispring::api::{
+    processor::{read_allocations, transform_allocations_to_cumulative_rounds},
+    structs::{CampaignConfig, JSONAllocation, RoundAmounts, VestingSchedule},
+    vesting::{
+        expand_schedule, merge_vesting_rounds, read_vesting_schedules, vesting_preview,
+        VestingCurve, VestingMerge,
+    },
+};
+use starknet_crypto::FieldElement;
+use std::str::FromStr;
+
+fn schedule(total: &str, start_round: u8, cliff: u8, duration: u8) -> VestingSchedule {
+    VestingSchedule {
+        address: "0x1".to_string(),
+        total: total.to_string(),
+        token: None,
+        start_round,
+        cliff,
+        duration,
+        curve: VestingCurve::Linear,
+    }
+}
+
+/// Tests that the rounding dust is released in the final round
+#[test]
+fn linear_dust_in_final_round() {
+    let res = expand_schedule(&schedule("100", 1, 0, 3)).unwrap();
+    assert_eq!(res, vec![(1, 33), (2, 33), (3, 34)]);
+
+    let res = expand_schedule(&schedule("12", 5, 0, 12)).unwrap();
+    assert!(res.iter().all(|(_, amount)| *amount == 1));
+    assert_eq!(res.last().unwrap().0, 16);
+
+    // Large totals don't overflow
+    let res = expand_schedule(&schedule(&u128::MAX.to_string(), 1, 0, 7)).unwrap();
+    assert_eq!(res.iter().map(|(_, a)| a).sum::<u128>(), u128::MAX);
+}
+
+/// Tests that nothing is released during the cliff and the first round after it catches up
+#[test]
+fn cliff_and_quadratic_curve() {
+    let res = expand_schedule(&schedule("100", 1, 2, 4)).unwrap();
+    assert_eq!(res, vec![(1, 0), (2, 0), (3, 75), (4, 25)]);
+
+    let mut quadratic = schedule("1000", 2, 1, 4);
+    quadratic.curve = VestingCurve::Quadratic;
+    let res = expand_schedule(&quadratic).unwrap();
+    assert_eq!(res, vec![(2, 0), (3, 249), (4, 312), (5, 439)]);
+}
+
+/// Tests that invalid schedules are rejected
+#[test]
+fn invalid_schedules() {
+    assert!(expand_schedule(&schedule("100", 0, 0, 3)).is_err());
+    assert!(expand_schedule(&schedule("100", 1, 0, 0)).is_err());
+    assert!(expand_schedule(&schedule("100", 1, 3, 3)).is_err());
+    assert!(expand_schedule(&schedule("100", 250, 0, 10)).is_err());
+    assert!(expand_schedule(&schedule("-1", 1, 0, 3)).is_err());
+
+    let mut invalid_address = schedule("100", 1, 0, 3);
+    invalid_address.address = "0xg".to_string();
+    assert!(expand_schedule(&invalid_address).is_err());
+}
+
+/// Tests that vested amounts are merged with the rounds of the input files, that rounds without a file aren't created
+/// and that invalid schedules are skipped
+#[test]
+fn merged_with_round_amounts() {
+    let mut round_amounts: Vec<RoundAmounts> = [1, 2]
+        .iter()
+        .map(|round| RoundAmounts {
+            round: *round,
+            amounts: vec![JSONAllocation {
+                address: "0x1".to_string(),
+                amount: "5".to_string(),
+                ..Default::default()
+            }],
+        })
+        .collect();
+    let merge = merge_vesting_rounds(
+        &mut round_amounts,
+        &[schedule("100", 0, 0, 3), schedule("100", 1, 0, 3)],
+    );
+    // The dust of the final round waits for its input file
+    assert_eq!(
+        merge,
+        VestingMerge {
+            invalid: vec![(0, "Vesting can't start from round 0".to_string())],
+            pending: vec![(1, 3, 34)],
+        }
+    );
+
+    let res = transform_allocations_to_cumulative_rounds(round_amounts, &CampaignConfig::default());
+    let one = FieldElement::from_str("0x1").unwrap();
+
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0].address_amount(one).unwrap(), 38);
+    assert_eq!(res[1].address_amount(one).unwrap(), 76);
+    assert_eq!(res[1].round_total_amount, 38);
+}
+
+/// Tests reading the schedules from the input folder and previewing them
+#[test]
+fn read_and_preview() {
+    let path = "./tests/test_vesting_input";
+    let schedules = read_vesting_schedules(path);
+    assert_eq!(schedules.len(), 2);
+
+    let preview = vesting_preview(&schedules, "0x02", &[1, 2, 3]).unwrap();
+    let amounts: Vec<&str> = preview.iter().map(|r| r.amount.as_str()).collect();
+    assert_eq!(amounts, vec!["0", "249", "312", "439"]);
+    // Only the rounds with an input file are released
+    let released: Vec<bool> = preview.iter().map(|r| r.released).collect();
+    assert_eq!(released, vec![true, true, false, false]);
+    assert_eq!(preview[3].cumulative_amount, "1000");
+    assert_eq!(
+        preview[0].token.as_deref(),
+        Some("0x0000000000000000000000000000000000000000000000000000000000004718")
+    );
+    assert!(vesting_preview(&schedules, "0x3", &[]).unwrap().is_empty());
+
+    // The schedules alone don't make rounds, the tranches of future rounds are only previewed
+    assert!(read_allocations(path.to_string()).is_empty());
+}
@@ -1,3 +1,5 @@
 mod test_token;
 #[cfg(test)]
 mod test;
+#[cfg(test)]
+mod test_golden_vectors;
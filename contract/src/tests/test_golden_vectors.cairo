@@ -0,0 +1,142 @@
+use alexandria_merkle_tree::merkle_tree::{
+    Hasher, MerkleTree, pedersen::PedersenHasherImpl, MerkleTreeTrait
+};
+use core::array::{ArrayTrait, SpanTrait};
+use core::serde::Serde;
+use distributor::contract::{IDistributorDispatcher, IDistributorDispatcherTrait};
+use snforge_std::{ContractClassTrait, declare, start_prank, CheatTarget};
+use snforge_std::fs::{FileTrait, read_json};
+
+const ADMIN_ADDR: felt252 = 0x42;
+
+// read_json serializes the values of each object in the alphabetical order of their keys,
+// so the members of these structs are in that order too
+
+#[derive(Drop, Serde)]
+struct LeafHash {
+    address: felt252,
+    amount: felt252,
+    leaf: felt252,
+}
+
+#[derive(Drop, Serde)]
+struct PairHash {
+    a: felt252,
+    b: felt252,
+    hash: felt252,
+}
+
+#[derive(Drop, Serde)]
+struct Proof {
+    address: felt252,
+    amount: felt252,
+    proof: Array<felt252>,
+}
+
+#[derive(Drop, Serde)]
+struct Tree {
+    levels: Array<Array<felt252>>,
+    proofs: Array<Proof>,
+    root: felt252,
+    size: felt252,
+}
+
+#[derive(Drop, Serde)]
+struct GoldenVectors {
+    leaf_hashes: Array<LeafHash>,
+    pair_hashes: Array<PairHash>,
+    trees: Array<Tree>,
+}
+
+fn read_vectors() -> GoldenVectors {
+    let file = FileTrait::new('data/golden_vectors.json');
+    let content = read_json(@file);
+    let mut span = content.span();
+    Serde::deserialize(ref span).expect('invalid golden vectors')
+}
+
+fn deploy() -> IDistributorDispatcher {
+    let mut calldata = ArrayTrait::new();
+    calldata.append(ADMIN_ADDR);
+
+    let contract = declare('Distributor');
+    let address = contract.deploy(@calldata).expect('unable to deploy distributor');
+
+    IDistributorDispatcher { contract_address: address }
+}
+
+// Without a proof the root is the leaf itself
+#[test]
+fn test_golden_leaf_hashes() {
+    let contract = deploy();
+    let vectors = read_vectors();
+    let mut leaves = vectors.leaf_hashes.span();
+    loop {
+        match leaves.pop_front() {
+            Option::Some(leaf) => {
+                let root = contract
+                    .get_root_for(
+                        (*leaf.address).try_into().unwrap(),
+                        (*leaf.amount).try_into().unwrap(),
+                        array![].span()
+                    );
+                assert(root == *leaf.leaf, 'wrong leaf hash');
+            },
+            Option::None => { break; }
+        };
+    };
+}
+
+#[test]
+fn test_golden_pair_hashes() {
+    let vectors = read_vectors();
+    let mut pairs = vectors.pair_hashes.span();
+    loop {
+        match pairs.pop_front() {
+            Option::Some(pair) => {
+                let mut merkle_tree: MerkleTree<Hasher> = MerkleTreeTrait::new();
+                let hash = merkle_tree.compute_root(*pair.a, array![*pair.b].span());
+                assert(hash == *pair.hash, 'wrong pair hash');
+            },
+            Option::None => { break; }
+        };
+    };
+}
+
+// Every proof of a tree has to give its root, which is then one of the roots of the contract
+#[test]
+fn test_golden_trees() {
+    let contract = deploy();
+    start_prank(CheatTarget::One(contract.contract_address), ADMIN_ADDR.try_into().unwrap());
+    let vectors = read_vectors();
+    let mut trees = vectors.trees.span();
+    let mut count = 0;
+    loop {
+        match trees.pop_front() {
+            Option::Some(tree) => {
+                contract.add_root(*tree.root);
+                count += 1;
+                let mut proofs = tree.proofs.span();
+                loop {
+                    match proofs.pop_front() {
+                        Option::Some(proof) => {
+                            let root = contract
+                                .get_root_for(
+                                    (*proof.address).try_into().unwrap(),
+                                    (*proof.amount).try_into().unwrap(),
+                                    proof.proof.span()
+                                );
+                            assert(root == *tree.root, 'wrong root for proof');
+                        },
+                        Option::None => { break; }
+                    };
+                };
+            },
+            Option::None => { break; }
+        };
+    };
+
+    let roots = contract.roots();
+    assert(roots.len() == count, 'wrong number of roots');
+    assert(*roots.at(count - 1) == *vectors.trees.at(count - 1).root, 'wrong last root');
+}